@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+use crate::{protocol::ServerPluginMessage, session::SessionError};
+
+/// Outcome of a request acknowledged by the host
+type AckOutcome = Result<(), SessionError>;
+
+/// Request ID and receiver for its acknowledgement
+type PendingAck = (u64, oneshot::Receiver<AckOutcome>);
+
+/// Requests sent to the host that are waiting for an acknowledgement
+#[derive(Default, Clone)]
+pub(crate) struct PendingRequests {
+    inner: Arc<PendingRequestsInner>,
+}
+
+#[derive(Default)]
+struct PendingRequestsInner {
+    /// Next ID to use for a request
+    next_id: AtomicU64,
+    /// Senders for the requests waiting for an acknowledgement
+    pending: Mutex<HashMap<u64, oneshot::Sender<AckOutcome>>>,
+}
+
+impl PendingRequests {
+    /// Creates a new unique request ID
+    pub fn next_id(&self) -> u64 {
+        self.inner.next_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Tracks the request `request_id` until it is acknowledged, requests
+    /// that are no longer being waited on are dropped
+    pub fn track(&self, request_id: u64) -> oneshot::Receiver<AckOutcome> {
+        let (tx, rx) = oneshot::channel();
+        let pending = &mut *self.inner.pending.lock();
+        pending.retain(|_, tx| !tx.is_closed());
+        pending.insert(request_id, tx);
+        rx
+    }
+
+    /// Stops tracking the request `request_id`
+    pub fn remove(&self, request_id: u64) {
+        self.inner.pending.lock().remove(&request_id);
+    }
+
+    /// Resolves the pending request for an acknowledgement or error from the host
+    pub fn apply(&self, msg: &ServerPluginMessage) {
+        let (request_id, outcome) = match msg {
+            ServerPluginMessage::Ack { request_id } => (*request_id, Ok(())),
+            ServerPluginMessage::Error {
+                request_id: Some(request_id),
+                code,
+                message,
+            } => (
+                *request_id,
+                Err(SessionError::Rejected {
+                    code: code.clone(),
+                    message: message.clone(),
+                }),
+            ),
+            _ => return,
+        };
+
+        if let Some(tx) = self.inner.pending.lock().remove(&request_id) {
+            _ = tx.send(outcome);
+        }
+    }
+
+    /// Drops all pending requests, waiting acknowledgements fail with [SessionError::Closed]
+    pub fn clear(&self) {
+        self.inner.pending.lock().clear();
+    }
+}
+
+/// Collects the requests sent within [PluginSessionHandle::with_ack](crate::PluginSessionHandle::with_ack)
+#[derive(Clone)]
+pub(crate) struct AckCollector {
+    requests: PendingRequests,
+    receivers: Arc<Mutex<Vec<PendingAck>>>,
+}
+
+impl AckCollector {
+    pub fn new(requests: PendingRequests) -> Self {
+        Self {
+            requests,
+            receivers: Default::default(),
+        }
+    }
+
+    /// Tracks the request `request_id` as part of the acknowledgement
+    pub fn track(&self, request_id: u64) {
+        let rx = self.requests.track(request_id);
+        self.receivers.lock().push((request_id, rx));
+    }
+
+    pub fn into_acknowledgement(self) -> Acknowledgement {
+        Acknowledgement {
+            requests: self.requests,
+            pending: std::mem::take(&mut *self.receivers.lock()),
+        }
+    }
+}
+
+/// Future resolving once the host has acknowledged the requests sent
+/// within [PluginSessionHandle::with_ack](crate::PluginSessionHandle::with_ack)
+///
+/// Resolves to the first [SessionError::Rejected] when the host rejects
+/// any of the requests, or [SessionError::Closed] when the session
+/// closes before the requests are acknowledged. Dropping the future,
+/// such as when it times out, stops tracking the requests
+#[must_use = "acknowledgements do nothing unless awaited"]
+pub struct Acknowledgement {
+    requests: PendingRequests,
+    pending: Vec<PendingAck>,
+}
+
+impl Drop for Acknowledgement {
+    fn drop(&mut self) {
+        for (request_id, _) in self.pending.drain(..) {
+            self.requests.remove(request_id);
+        }
+    }
+}
+
+impl Future for Acknowledgement {
+    type Output = AckOutcome;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        while let Some((_, rx)) = this.pending.first_mut() {
+            let outcome = match Pin::new(rx).poll(cx) {
+                Poll::Ready(outcome) => outcome,
+                Poll::Pending => return Poll::Pending,
+            };
+
+            this.pending.remove(0);
+
+            match outcome {
+                Ok(Ok(())) => {}
+                Ok(Err(error)) => return Poll::Ready(Err(error)),
+                Err(_) => return Poll::Ready(Err(SessionError::Closed)),
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(requests: &PendingRequests) -> usize {
+        requests.inner.pending.lock().len()
+    }
+
+    #[test]
+    fn request_ids_are_unique() {
+        let requests = PendingRequests::default();
+        assert_eq!(requests.next_id(), 1);
+        assert_eq!(requests.next_id(), 2);
+        assert_eq!(requests.clone().next_id(), 3);
+    }
+
+    #[test]
+    fn error_rejects_request() {
+        let requests = PendingRequests::default();
+        let mut rx = requests.track(1);
+
+        requests.apply(&ServerPluginMessage::Error {
+            request_id: None,
+            code: "invalid".to_string(),
+            message: "unrelated".to_string(),
+        });
+        assert!(rx.try_recv().is_err());
+
+        requests.apply(&ServerPluginMessage::Error {
+            request_id: Some(1),
+            code: "invalid".to_string(),
+            message: "invalid request".to_string(),
+        });
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(Err(SessionError::Rejected { code, message }))
+                if code == "invalid" && message == "invalid request"
+        ));
+        assert_eq!(pending(&requests), 0);
+    }
+
+    #[test]
+    fn abandoned_requests_are_dropped() {
+        let requests = PendingRequests::default();
+        drop(requests.track(1));
+        let _rx = requests.track(2);
+        assert_eq!(pending(&requests), 1);
+    }
+
+    #[test]
+    fn dropped_acknowledgement_stops_tracking() {
+        let requests = PendingRequests::default();
+        let acks = AckCollector::new(requests.clone());
+        acks.track(1);
+        acks.track(2);
+
+        let ack = acks.into_acknowledgement();
+        assert_eq!(pending(&requests), 2);
+
+        drop(ack);
+        assert_eq!(pending(&requests), 0);
+    }
+
+    #[tokio::test]
+    async fn acknowledgement_resolves_to_first_rejection() {
+        let requests = PendingRequests::default();
+        let acks = AckCollector::new(requests.clone());
+        acks.track(1);
+        acks.track(2);
+        let ack = acks.into_acknowledgement();
+
+        requests.apply(&ServerPluginMessage::Ack { request_id: 1 });
+        requests.apply(&ServerPluginMessage::Error {
+            request_id: Some(2),
+            code: "invalid".to_string(),
+            message: "invalid request".to_string(),
+        });
+
+        assert!(matches!(ack.await, Err(SessionError::Rejected { .. })));
+    }
+}
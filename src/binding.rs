@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+use crate::{
+    inspector::Inspector,
+    protocol::{ActionId, JsonObject, TileId},
+    session::PluginSessionHandle,
+};
+
+/// Tile properties that can be bound to an inspector form using
+/// [InspectorRouter::bind_properties](crate::InspectorRouter::bind_properties)
+pub trait BindableProperties: Serialize + DeserializeOwned + 'static {
+    /// Validates the properties after an edit from the inspector,
+    /// edits that fail validation are not applied
+    fn validate(&self) -> Result<(), BindingError> {
+        Ok(())
+    }
+}
+
+/// Error for an edit from the inspector that was rejected
+#[derive(Debug, Clone, Error, Serialize, Deserialize)]
+#[error("{message}")]
+pub struct BindingError {
+    /// Name of the field the error is for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// Error message
+    pub message: String,
+}
+
+impl BindingError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            field: None,
+            message: message.into(),
+        }
+    }
+
+    /// Creates an error for a specific `field`
+    pub fn field(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: Some(field.into()),
+            message: message.into(),
+        }
+    }
+}
+
+/// Envelope for messages exchanged with an inspector bound to the
+/// tile properties
+///
+/// ```json
+/// { "binding": "Refresh" }
+/// { "binding": "Update", "fields": { "threshold": 90 } }
+/// { "binding": "Properties", "properties": { "threshold": 90, "label": "CPU" } }
+/// { "binding": "Error", "error": { "field": "threshold", "message": "must be below 100" } }
+/// ```
+///
+/// The inspector sends `Refresh` and `Update` messages, the plugin
+/// replies with `Properties` or `Error`. `Properties` is also sent
+/// when the inspector is first opened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "binding")]
+pub enum BindingMessage {
+    /// Request for the current properties
+    Refresh,
+    /// Edit to some of the fields of the properties
+    Update { fields: JsonObject },
+    /// Current properties of the tile
+    Properties { properties: JsonObject },
+    /// Edit from the inspector was rejected
+    Error { error: BindingError },
+}
+
+impl BindingMessage {
+    /// Key present on all binding envelopes, used to distinguish them
+    /// from other inspector messages
+    pub const KEY: &str = "binding";
+}
+
+/// Handler that validates and applies an edit with the typed properties erased,
+/// receives the merged properties and the edited fields
+type BindingHandler<P> = Box<
+    dyn Fn(
+        &mut P,
+        &PluginSessionHandle,
+        &Inspector,
+        &JsonObject,
+        JsonObject,
+    ) -> Result<(), BindingError>,
+>;
+
+/// Bindings between inspectors and the properties of their tile
+pub(crate) struct PropertyBindings<P> {
+    /// Handlers for edits to each bound action
+    handlers: HashMap<ActionId, BindingHandler<P>>,
+    /// Last known properties for tiles with an open bound inspector
+    properties: Mutex<HashMap<TileId, JsonObject>>,
+    /// Edits received before the properties of the tile were known, applied
+    /// once the properties are received
+    queued: Mutex<HashMap<TileId, JsonObject>>,
+}
+
+impl<P> Default for PropertyBindings<P> {
+    fn default() -> Self {
+        Self {
+            handlers: Default::default(),
+            properties: Default::default(),
+            queued: Default::default(),
+        }
+    }
+}
+
+impl<P> PropertyBindings<P> {
+    pub fn bind<T, F>(&mut self, action_id: ActionId, on_change: F)
+    where
+        T: BindableProperties,
+        F: Fn(&mut P, &PluginSessionHandle, &Inspector, &T) + 'static,
+    {
+        self.handlers.insert(
+            action_id,
+            Box::new(move |plugin, session, inspector, properties, fields| {
+                let value: T =
+                    serde_json::from_value(serde_json::Value::Object(properties.clone()))
+                        .map_err(|cause| BindingError::new(cause.to_string()))?;
+                value.validate()?;
+
+                session
+                    .set_tile_properties_partial(inspector.ctx.tile_id, fields)
+                    .map_err(|cause| BindingError::new(cause.to_string()))?;
+
+                on_change(plugin, session, inspector, &value);
+                Ok(())
+            }),
+        );
+    }
+
+    fn is_bound(&self, inspector: &Inspector) -> bool {
+        self.handlers.contains_key(&inspector.ctx.action_id)
+    }
+
+    /// Requests the current properties when a bound inspector is opened
+    pub fn handle_open(&self, inspector: &Inspector) {
+        if !self.is_bound(inspector) {
+            return;
+        }
+
+        if let Err(cause) = inspector
+            .session
+            .request_tile_properties(inspector.ctx.tile_id)
+        {
+            tracing::error!(?cause, "failed to request bound tile properties");
+        }
+    }
+
+    /// Drops the stored properties and queued edits when a bound inspector is closed
+    pub fn handle_close(&self, inspector: &Inspector) {
+        self.properties.lock().remove(&inspector.ctx.tile_id);
+        self.queued.lock().remove(&inspector.ctx.tile_id);
+    }
+
+    /// Stores the latest properties for a tile, pushing them to the
+    /// inspector when a bound inspector is open for the tile
+    ///
+    /// Edits queued while the properties were unknown are applied
+    /// on top of the received properties
+    pub fn handle_tile_properties(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        tile_id: TileId,
+        properties: &serde_json::Value,
+    ) {
+        let Some(inspector) = session.inspector_for_tile(tile_id) else {
+            return;
+        };
+
+        if !self.is_bound(&inspector) {
+            return;
+        }
+
+        let properties = properties.as_object().cloned().unwrap_or_default();
+        self.properties.lock().insert(tile_id, properties.clone());
+
+        let queued = self.queued.lock().remove(&tile_id);
+        match queued {
+            Some(fields) => self.apply_update(plugin, session, &inspector, fields),
+            None => send(&inspector, BindingMessage::Properties { properties }),
+        }
+    }
+
+    /// Validates and applies an edit on top of the known properties of the tile
+    fn apply_update(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        inspector: &Inspector,
+        fields: JsonObject,
+    ) {
+        let Some(handler) = self.handlers.get(&inspector.ctx.action_id) else {
+            return;
+        };
+
+        let tile_id = inspector.ctx.tile_id;
+        let mut properties = self
+            .properties
+            .lock()
+            .get(&tile_id)
+            .cloned()
+            .unwrap_or_default();
+        properties.extend(fields.clone());
+
+        if let Err(error) = handler(plugin, session, inspector, &properties, fields) {
+            send(inspector, BindingMessage::Error { error });
+            return;
+        }
+
+        self.properties.lock().insert(tile_id, properties.clone());
+        send(inspector, BindingMessage::Properties { properties });
+    }
+
+    /// Handles a binding message from the `inspector`, returns the message
+    /// back when the inspector is not bound
+    pub fn handle(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        inspector: &Inspector,
+        message: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        if !self.is_bound(inspector) {
+            return Some(message);
+        }
+
+        let message: BindingMessage = match serde_json::from_value(message) {
+            Ok(value) => value,
+            Err(cause) => {
+                tracing::warn!(?cause, "invalid binding message from inspector");
+                let error = BindingError::new(cause.to_string());
+                send(inspector, BindingMessage::Error { error });
+                return None;
+            }
+        };
+
+        let tile_id = inspector.ctx.tile_id;
+
+        match message {
+            BindingMessage::Refresh => {
+                if let Err(cause) = session.request_tile_properties(tile_id) {
+                    tracing::error!(?cause, "failed to request bound tile properties");
+                }
+            }
+
+            BindingMessage::Update { fields } => {
+                if self.properties.lock().contains_key(&tile_id) {
+                    self.apply_update(plugin, session, inspector, fields);
+                    return None;
+                }
+
+                // Properties are not known yet, queue the edit until they are received
+                // so the edit is validated against the complete properties
+                let first = {
+                    let queued = &mut *self.queued.lock();
+                    let first = !queued.contains_key(&tile_id);
+                    queued.entry(tile_id).or_default().extend(fields);
+                    first
+                };
+
+                if first && let Err(cause) = session.request_tile_properties(tile_id) {
+                    tracing::error!(?cause, "failed to request bound tile properties");
+                }
+            }
+
+            // Messages only sent by the plugin
+            BindingMessage::Properties { .. } | BindingMessage::Error { .. } => {
+                tracing::warn!("unexpected binding message from inspector");
+            }
+        }
+
+        None
+    }
+}
+
+fn send(inspector: &Inspector, message: BindingMessage) {
+    if let Err(cause) = inspector.send(message) {
+        tracing::error!(?cause, "failed to send binding message to inspector");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        protocol::InspectorContext,
+        subscription::Subscriptions,
+        ws::{WsMessage, WsRx},
+    };
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Thresholds {
+        threshold: u32,
+        label: String,
+    }
+
+    impl BindableProperties for Thresholds {
+        fn validate(&self) -> Result<(), BindingError> {
+            if self.threshold >= 100 {
+                return Err(BindingError::field("threshold", "must be below 100"));
+            }
+            Ok(())
+        }
+    }
+
+    fn bindings() -> PropertyBindings<Vec<u32>> {
+        let mut bindings = PropertyBindings::default();
+        bindings.bind(
+            "action".to_string(),
+            |changes: &mut Vec<u32>, _, _, value: &Thresholds| changes.push(value.threshold),
+        );
+        bindings
+    }
+
+    /// Opens an inspector for a bound tile
+    fn inspector() -> (Inspector, WsRx) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let session = PluginSessionHandle::new(tx, Subscriptions::default());
+        let ctx = InspectorContext {
+            profile_id: Uuid::new_v4(),
+            folder_id: Uuid::new_v4(),
+            plugin_id: "plugin".to_string(),
+            action_id: "action".to_string(),
+            tile_id: Uuid::new_v4(),
+        };
+        session.inspector_opened(ctx.clone());
+        (Inspector { session, ctx }, rx)
+    }
+
+    /// Drains the sent messages as JSON
+    fn sent(rx: &mut WsRx) -> Vec<serde_json::Value> {
+        let mut messages = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            if let WsMessage::Text(text) = message {
+                messages.push(serde_json::from_str(&text).unwrap());
+            }
+        }
+        messages
+    }
+
+    fn receive_properties(
+        bindings: &PropertyBindings<Vec<u32>>,
+        changes: &mut Vec<u32>,
+        inspector: &Inspector,
+        properties: serde_json::Value,
+    ) {
+        bindings.handle_tile_properties(
+            changes,
+            &inspector.session,
+            inspector.ctx.tile_id,
+            &properties,
+        );
+    }
+
+    #[test]
+    fn pushes_properties_on_open() {
+        let bindings = bindings();
+        let (inspector, mut rx) = inspector();
+        let mut changes = Vec::new();
+
+        bindings.handle_open(&inspector);
+        let sent_messages = sent(&mut rx);
+        assert_eq!(sent_messages[0]["type"], "GetTileProperties");
+        assert_eq!(sent_messages[0]["tile_id"], json!(inspector.ctx.tile_id));
+
+        let properties = json!({ "threshold": 50, "label": "CPU" });
+        receive_properties(&bindings, &mut changes, &inspector, properties.clone());
+
+        let sent_messages = sent(&mut rx);
+        assert_eq!(sent_messages[0]["type"], "SendToInspector");
+        assert_eq!(
+            sent_messages[0]["message"],
+            json!({ "binding": "Properties", "properties": properties })
+        );
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn ignores_unbound_inspectors() {
+        let bindings = bindings();
+        let (mut inspector, mut rx) = inspector();
+        inspector.ctx.action_id = "other".to_string();
+        let mut changes = Vec::new();
+
+        bindings.handle_open(&inspector);
+        let message = json!({ "binding": "Refresh" });
+        assert_eq!(
+            bindings.handle(
+                &mut changes,
+                &inspector.session,
+                &inspector,
+                message.clone()
+            ),
+            Some(message)
+        );
+        assert!(sent(&mut rx).is_empty());
+    }
+
+    #[test]
+    fn applies_partial_updates() {
+        let bindings = bindings();
+        let (inspector, mut rx) = inspector();
+        let mut changes = Vec::new();
+
+        let properties = json!({ "threshold": 50, "label": "CPU" });
+        receive_properties(&bindings, &mut changes, &inspector, properties);
+        sent(&mut rx);
+
+        let update = json!({ "binding": "Update", "fields": { "threshold": 90 } });
+        assert!(
+            bindings
+                .handle(&mut changes, &inspector.session, &inspector, update)
+                .is_none()
+        );
+        assert_eq!(changes, [90]);
+
+        let sent_messages = sent(&mut rx);
+        assert_eq!(sent_messages[0]["type"], "SetTileProperties");
+        assert_eq!(sent_messages[0]["properties"], json!({ "threshold": 90 }));
+        assert_eq!(sent_messages[0]["partial"], true);
+        assert_eq!(
+            sent_messages[1]["message"],
+            json!({
+                "binding": "Properties",
+                "properties": { "threshold": 90, "label": "CPU" }
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_updates() {
+        let bindings = bindings();
+        let (inspector, mut rx) = inspector();
+        let mut changes = Vec::new();
+
+        let properties = json!({ "threshold": 50, "label": "CPU" });
+        receive_properties(&bindings, &mut changes, &inspector, properties);
+        sent(&mut rx);
+
+        let update = json!({ "binding": "Update", "fields": { "threshold": 100 } });
+        bindings.handle(&mut changes, &inspector.session, &inspector, update);
+        assert!(changes.is_empty());
+
+        let sent_messages = sent(&mut rx);
+        assert_eq!(sent_messages.len(), 1);
+        assert_eq!(
+            sent_messages[0]["message"],
+            json!({
+                "binding": "Error",
+                "error": { "field": "threshold", "message": "must be below 100" }
+            })
+        );
+
+        // Rejected edits are not applied to the known properties
+        let update = json!({ "binding": "Update", "fields": { "label": "GPU" } });
+        bindings.handle(&mut changes, &inspector.session, &inspector, update);
+        assert_eq!(changes, [50]);
+    }
+
+    #[test]
+    fn queues_updates_until_properties_are_known() {
+        let bindings = bindings();
+        let (inspector, mut rx) = inspector();
+        let mut changes = Vec::new();
+
+        let update = json!({ "binding": "Update", "fields": { "threshold": 90 } });
+        bindings.handle(&mut changes, &inspector.session, &inspector, update);
+        let update = json!({ "binding": "Update", "fields": { "label": "GPU" } });
+        bindings.handle(&mut changes, &inspector.session, &inspector, update);
+        assert!(changes.is_empty());
+
+        // Properties are only requested once for the queued edits
+        let sent_messages = sent(&mut rx);
+        assert_eq!(sent_messages.len(), 1);
+        assert_eq!(sent_messages[0]["type"], "GetTileProperties");
+
+        let properties = json!({ "threshold": 50, "label": "CPU" });
+        receive_properties(&bindings, &mut changes, &inspector, properties);
+        assert_eq!(changes, [90]);
+
+        let sent_messages = sent(&mut rx);
+        assert_eq!(
+            sent_messages[0]["properties"],
+            json!({ "threshold": 90, "label": "GPU" })
+        );
+        assert_eq!(
+            sent_messages[1]["message"]["properties"],
+            json!({ "threshold": 90, "label": "GPU" })
+        );
+    }
+
+    #[test]
+    fn reports_invalid_messages() {
+        let bindings = bindings();
+        let (inspector, mut rx) = inspector();
+        let mut changes = Vec::new();
+
+        let message = json!({ "binding": "Delete" });
+        assert!(
+            bindings
+                .handle(&mut changes, &inspector.session, &inspector, message)
+                .is_none()
+        );
+        assert_eq!(sent(&mut rx)[0]["message"]["binding"], "Error");
+    }
+}
@@ -0,0 +1,867 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    inspector::Inspector,
+    protocol::TileId,
+    session::{PluginSessionHandle, SessionError},
+};
+
+/// Unique ID for a blob transfer
+pub type BlobId = Uuid;
+
+/// Envelope for the messages of a chunked blob transfer
+///
+/// Large payloads are split into chunks that are sent as separate
+/// messages so that other messages are not blocked behind a single
+/// huge message on the socket
+///
+/// ```json
+/// { "blob": "Start", "id": "c5a2...", "size": 1048576, "chunks": 22, "name": "log.txt", "mime_type": "text/plain" }
+/// { "blob": "Chunk", "id": "c5a2...", "seq": 0, "data": "SGVsbG8..." }
+/// { "blob": "End", "id": "c5a2...", "checksum": 2290113923 }
+/// { "blob": "Abort", "id": "c5a2...", "reason": "checksum mismatch" }
+/// ```
+///
+/// Chunks are sent in order starting at a `seq` of zero, `data` is
+/// the standard base64 encoding of the chunk bytes and `checksum` is
+/// the CRC-32 of the complete blob. The same envelope is used for
+/// uploads from the inspector to the plugin, see
+/// [InspectorRouter::upload](crate::InspectorRouter::upload)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "blob")]
+pub enum BlobMessage {
+    /// Start of a new transfer
+    Start {
+        id: BlobId,
+        /// Total size of the blob in bytes
+        size: u64,
+        /// Number of chunks that will be sent
+        chunks: u32,
+        /// Name of the blob, such as a file name
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// MIME type of the blob contents
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
+
+    /// Chunk of the blob data
+    Chunk {
+        id: BlobId,
+        /// Index of the chunk within the blob
+        seq: u32,
+        /// Base64 encoded chunk bytes
+        data: String,
+    },
+
+    /// End of the transfer
+    End {
+        id: BlobId,
+        /// CRC-32 checksum of the complete blob
+        checksum: u32,
+    },
+
+    /// Transfer was cancelled or rejected by either side
+    Abort { id: BlobId, reason: String },
+}
+
+impl BlobMessage {
+    /// Key present on all blob envelopes, used to distinguish them
+    /// from other messages
+    pub const KEY: &str = "blob";
+
+    /// ID of the transfer the message belongs to
+    pub fn id(&self) -> BlobId {
+        match self {
+            BlobMessage::Start { id, .. }
+            | BlobMessage::Chunk { id, .. }
+            | BlobMessage::End { id, .. }
+            | BlobMessage::Abort { id, .. } => *id,
+        }
+    }
+}
+
+/// Options for sending a blob
+#[derive(Debug, Clone)]
+pub struct BlobOptions {
+    /// Name of the blob, such as a file name
+    pub name: Option<String>,
+    /// MIME type of the blob contents
+    pub mime_type: Option<String>,
+    /// Maximum number of bytes in each chunk before encoding
+    pub chunk_size: usize,
+}
+
+impl Default for BlobOptions {
+    fn default() -> Self {
+        Self {
+            name: None,
+            mime_type: None,
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl BlobOptions {
+    /// Default number of bytes in each chunk
+    pub const DEFAULT_CHUNK_SIZE: usize = 48 * 1024;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    /// Sets the chunk size, sizes of zero are raised to one byte
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+}
+
+/// Sends `data` as a chunked blob using `send`, yielding to other
+/// tasks between chunks so other messages can be sent in between
+pub(crate) async fn send_blob<F>(
+    send: F,
+    data: &[u8],
+    options: BlobOptions,
+) -> Result<BlobId, SessionError>
+where
+    F: Fn(BlobMessage) -> Result<(), SessionError>,
+{
+    let id = Uuid::new_v4();
+    let chunk_size = options.chunk_size.max(1);
+    let chunks = data.chunks(chunk_size);
+
+    send(BlobMessage::Start {
+        id,
+        size: data.len() as u64,
+        chunks: chunks.len() as u32,
+        name: options.name,
+        mime_type: options.mime_type,
+    })?;
+
+    for (seq, chunk) in chunks.enumerate() {
+        tokio::task::yield_now().await;
+
+        send(BlobMessage::Chunk {
+            id,
+            seq: seq as u32,
+            data: BASE64_STANDARD.encode(chunk),
+        })?;
+    }
+
+    send(BlobMessage::End {
+        id,
+        checksum: crc32fast::hash(data),
+    })?;
+
+    Ok(id)
+}
+
+/// Blob received through a chunked transfer
+#[derive(Debug, Clone)]
+pub struct Blob {
+    pub id: BlobId,
+    /// Name of the blob, such as a file name
+    pub name: Option<String>,
+    /// MIME type of the blob contents
+    pub mime_type: Option<String>,
+    /// Blob contents
+    pub data: Vec<u8>,
+}
+
+/// Error from reassembling a blob
+#[derive(Debug, Error)]
+pub enum BlobError {
+    /// Received a message for a transfer that was not started
+    #[error("unknown blob {0}")]
+    UnknownBlob(BlobId),
+
+    /// Received a start message for a transfer that was already started
+    #[error("blob {0} was already started")]
+    AlreadyStarted(BlobId),
+
+    /// Blob is larger than the maximum allowed size
+    #[error("blob size {size} exceeds the maximum of {max} bytes")]
+    TooLarge { size: u64, max: u64 },
+
+    /// Chunk was received out of order
+    #[error("expected chunk {expected} but got chunk {received}")]
+    OutOfOrder { expected: u32, received: u32 },
+
+    /// Chunk data was not valid base64
+    #[error("invalid chunk data: {0}")]
+    InvalidData(#[from] base64::DecodeError),
+
+    /// Received data did not match the size or chunk count from the start message
+    #[error("expected {expected} bytes but got {received}")]
+    SizeMismatch { expected: u64, received: u64 },
+
+    /// Checksum of the received data did not match the sender checksum
+    #[error("checksum mismatch")]
+    ChecksumMismatch,
+
+    /// Transfer was aborted by the sender
+    #[error("blob aborted: {0}")]
+    Aborted(String),
+}
+
+/// Partially received blob
+struct PartialBlob {
+    size: u64,
+    chunks: u32,
+    name: Option<String>,
+    mime_type: Option<String>,
+    next_seq: u32,
+    data: Vec<u8>,
+}
+
+/// Reassembles blobs from the messages of chunked transfers
+///
+/// Messages from multiple transfers can be interleaved, each transfer
+/// is tracked separately by its ID. Transfers that fail are discarded.
+/// Blobs larger than the maximum size are rejected when the transfer
+/// starts, the buffer for a blob grows as its chunks are received
+pub struct BlobAssembler {
+    /// Maximum size of a blob in bytes
+    max_size: u64,
+    /// Transfers in progress
+    partial: HashMap<BlobId, PartialBlob>,
+}
+
+impl Default for BlobAssembler {
+    fn default() -> Self {
+        Self::with_max_size(Self::DEFAULT_MAX_SIZE)
+    }
+}
+
+impl BlobAssembler {
+    /// Default maximum blob size
+    pub const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+    /// Creates an assembler that rejects blobs larger than [BlobAssembler::DEFAULT_MAX_SIZE]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an assembler that rejects blobs larger than `max_size` bytes
+    pub fn with_max_size(max_size: u64) -> Self {
+        Self {
+            max_size,
+            partial: Default::default(),
+        }
+    }
+
+    /// Handles a message from a transfer, returns the blob once
+    /// the transfer has completed
+    pub fn handle(&mut self, message: BlobMessage) -> Result<Option<Blob>, BlobError> {
+        let id = message.id();
+        let result = self.apply(message);
+
+        if result.is_err() {
+            self.partial.remove(&id);
+        }
+
+        result
+    }
+
+    /// Discards the transfer for `id`
+    pub fn remove(&mut self, id: &BlobId) {
+        self.partial.remove(id);
+    }
+
+    /// Number of transfers in progress
+    pub fn pending(&self) -> usize {
+        self.partial.len()
+    }
+
+    fn apply(&mut self, message: BlobMessage) -> Result<Option<Blob>, BlobError> {
+        match message {
+            BlobMessage::Start {
+                id,
+                size,
+                chunks,
+                name,
+                mime_type,
+            } => {
+                if self.partial.contains_key(&id) {
+                    return Err(BlobError::AlreadyStarted(id));
+                }
+
+                if size > self.max_size {
+                    return Err(BlobError::TooLarge {
+                        size,
+                        max: self.max_size,
+                    });
+                }
+
+                self.partial.insert(
+                    id,
+                    PartialBlob {
+                        size,
+                        chunks,
+                        name,
+                        mime_type,
+                        next_seq: 0,
+                        data: Vec::new(),
+                    },
+                );
+
+                Ok(None)
+            }
+
+            BlobMessage::Chunk { id, seq, data } => {
+                let partial = self
+                    .partial
+                    .get_mut(&id)
+                    .ok_or(BlobError::UnknownBlob(id))?;
+
+                if seq != partial.next_seq {
+                    return Err(BlobError::OutOfOrder {
+                        expected: partial.next_seq,
+                        received: seq,
+                    });
+                }
+
+                let before = partial.data.len();
+                BASE64_STANDARD.decode_vec(data, &mut partial.data)?;
+
+                let received = partial.data.len() as u64;
+                if received > partial.size {
+                    return Err(BlobError::SizeMismatch {
+                        expected: partial.size,
+                        received,
+                    });
+                }
+
+                tracing::trace!(%id, seq, bytes = received as usize - before, "received blob chunk");
+                partial.next_seq += 1;
+                Ok(None)
+            }
+
+            BlobMessage::End { id, checksum } => {
+                let partial = self.partial.remove(&id).ok_or(BlobError::UnknownBlob(id))?;
+
+                let received = partial.data.len() as u64;
+                if received != partial.size || partial.next_seq != partial.chunks {
+                    return Err(BlobError::SizeMismatch {
+                        expected: partial.size,
+                        received,
+                    });
+                }
+
+                if crc32fast::hash(&partial.data) != checksum {
+                    return Err(BlobError::ChecksumMismatch);
+                }
+
+                Ok(Some(Blob {
+                    id,
+                    name: partial.name,
+                    mime_type: partial.mime_type,
+                    data: partial.data,
+                }))
+            }
+
+            BlobMessage::Abort { id, reason } => {
+                self.partial.remove(&id);
+                Err(BlobError::Aborted(reason))
+            }
+        }
+    }
+}
+
+/// Time after the last message from a sender before its uploads
+/// in progress are discarded
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Reassembles uploads received from inspectors or displays, keeping
+/// the transfers for each sender separate
+///
+/// Senders are dropped once they have no uploads in progress, or when
+/// they have not sent a message within the timeout so that a sender
+/// that stops mid-transfer does not keep its buffers forever
+pub(crate) struct Uploads<K> {
+    /// Time without messages before a sender is discarded
+    timeout: Duration,
+    senders: Mutex<HashMap<K, UploadSender>>,
+}
+
+/// Uploads in progress from a single sender
+struct UploadSender {
+    assembler: BlobAssembler,
+    /// When the last message was received from the sender
+    last_message: Instant,
+}
+
+impl<K> Default for Uploads<K> {
+    fn default() -> Self {
+        Self::with_timeout(UPLOAD_TIMEOUT)
+    }
+}
+
+impl<K> Uploads<K> {
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            senders: Default::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash> Uploads<K> {
+    /// Drops the uploads in progress from the sender `key`
+    pub fn remove(&self, key: &K) {
+        self.senders.lock().remove(key);
+    }
+
+    /// Handles a blob `message` from the sender `key`, returns the blob once
+    /// the upload has completed. Failed uploads are aborted by sending an
+    /// [BlobMessage::Abort] back to the sender using `send`
+    pub fn receive<F>(&self, key: K, message: serde_json::Value, send: F) -> Option<Blob>
+    where
+        F: Fn(BlobMessage) -> Result<(), SessionError>,
+    {
+        let message: BlobMessage = match serde_json::from_value(message) {
+            Ok(value) => value,
+            Err(cause) => {
+                tracing::warn!(?cause, "invalid blob message");
+                return None;
+            }
+        };
+
+        let id = message.id();
+        let aborted = matches!(message, BlobMessage::Abort { .. });
+
+        let result = {
+            let senders = &mut *self.senders.lock();
+            let now = Instant::now();
+
+            senders.retain(|_, sender| now.duration_since(sender.last_message) < self.timeout);
+
+            let mut entry = match senders.entry(key) {
+                Entry::Occupied(entry) => entry,
+                Entry::Vacant(entry) => entry.insert_entry(UploadSender {
+                    assembler: BlobAssembler::default(),
+                    last_message: now,
+                }),
+            };
+
+            let sender = entry.get_mut();
+            sender.last_message = now;
+
+            let result = sender.assembler.handle(message);
+            if sender.assembler.pending() == 0 {
+                entry.remove();
+            }
+
+            result
+        };
+
+        match result {
+            Ok(blob) => blob,
+            // Sender cancelled the upload, nothing to report back
+            Err(_) if aborted => None,
+            Err(cause) => {
+                tracing::warn!(?cause, "failed to receive upload");
+
+                let reason = cause.to_string();
+                if let Err(cause) = send(BlobMessage::Abort { id, reason }) {
+                    tracing::error!(?cause, "failed to send blob abort");
+                }
+
+                None
+            }
+        }
+    }
+}
+
+/// Handler for a completed upload from an inspector
+type UploadHandler<P> = Box<dyn Fn(&mut P, &PluginSessionHandle, &Inspector, Blob)>;
+
+/// Uploads received from inspectors
+pub(crate) struct BlobUploads<P> {
+    /// Handler for completed uploads
+    handler: Option<UploadHandler<P>>,
+    /// Transfers in progress for each tile
+    uploads: Uploads<TileId>,
+}
+
+impl<P> Default for BlobUploads<P> {
+    fn default() -> Self {
+        Self {
+            handler: None,
+            uploads: Default::default(),
+        }
+    }
+}
+
+impl<P> BlobUploads<P> {
+    pub fn set_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(&mut P, &PluginSessionHandle, &Inspector, Blob) + 'static,
+    {
+        self.handler = Some(Box::new(handler));
+    }
+
+    /// Drops any uploads in progress when an inspector is closed
+    pub fn handle_close(&self, inspector: &Inspector) {
+        self.uploads.remove(&inspector.ctx.tile_id);
+    }
+
+    /// Handles a blob message from the `inspector`, returns the message
+    /// back when no upload handler is registered
+    pub fn handle(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        inspector: &Inspector,
+        message: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        let Some(handler) = &self.handler else {
+            return Some(message);
+        };
+
+        let blob = self
+            .uploads
+            .receive(inspector.ctx.tile_id, message, |message| {
+                inspector.send(message)
+            });
+
+        if let Some(blob) = blob {
+            handler(plugin, session, inspector, blob);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    fn start(id: BlobId, data: &[u8], chunks: u32) -> BlobMessage {
+        BlobMessage::Start {
+            id,
+            size: data.len() as u64,
+            chunks,
+            name: Some("test.txt".to_string()),
+            mime_type: None,
+        }
+    }
+
+    fn chunk(id: BlobId, seq: u32, data: &[u8]) -> BlobMessage {
+        BlobMessage::Chunk {
+            id,
+            seq,
+            data: BASE64_STANDARD.encode(data),
+        }
+    }
+
+    fn end(id: BlobId, data: &[u8]) -> BlobMessage {
+        BlobMessage::End {
+            id,
+            checksum: crc32fast::hash(data),
+        }
+    }
+
+    #[test]
+    fn assembles_chunks() {
+        let mut assembler = BlobAssembler::new();
+        let id = Uuid::new_v4();
+
+        assert!(
+            assembler
+                .handle(start(id, b"hello world", 2))
+                .unwrap()
+                .is_none()
+        );
+        assert!(assembler.handle(chunk(id, 0, b"hello ")).unwrap().is_none());
+        assert!(assembler.handle(chunk(id, 1, b"world")).unwrap().is_none());
+
+        let blob = assembler.handle(end(id, b"hello world")).unwrap().unwrap();
+        assert_eq!(blob.id, id);
+        assert_eq!(blob.name.as_deref(), Some("test.txt"));
+        assert_eq!(blob.data, b"hello world");
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    #[test]
+    fn rejects_out_of_order_chunks() {
+        let mut assembler = BlobAssembler::new();
+        let id = Uuid::new_v4();
+
+        assembler.handle(start(id, b"abcd", 2)).unwrap();
+        assert!(matches!(
+            assembler.handle(chunk(id, 1, b"cd")),
+            Err(BlobError::OutOfOrder {
+                expected: 0,
+                received: 1
+            })
+        ));
+
+        // Failed transfers are discarded
+        assert_eq!(assembler.pending(), 0);
+        assert!(matches!(
+            assembler.handle(chunk(id, 0, b"ab")),
+            Err(BlobError::UnknownBlob(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicate_chunks() {
+        let mut assembler = BlobAssembler::new();
+        let id = Uuid::new_v4();
+
+        assembler.handle(start(id, b"abcd", 2)).unwrap();
+        assembler.handle(chunk(id, 0, b"ab")).unwrap();
+        assert!(matches!(
+            assembler.handle(chunk(id, 0, b"ab")),
+            Err(BlobError::OutOfOrder {
+                expected: 1,
+                received: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicate_start() {
+        let mut assembler = BlobAssembler::new();
+        let id = Uuid::new_v4();
+
+        assembler.handle(start(id, b"ab", 1)).unwrap();
+        assert!(matches!(
+            assembler.handle(start(id, b"ab", 1)),
+            Err(BlobError::AlreadyStarted(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_checksum_mismatch() {
+        let mut assembler = BlobAssembler::new();
+        let id = Uuid::new_v4();
+
+        assembler.handle(start(id, b"abcd", 1)).unwrap();
+        assembler.handle(chunk(id, 0, b"abcd")).unwrap();
+        assert!(matches!(
+            assembler.handle(end(id, b"abce")),
+            Err(BlobError::ChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn rejects_size_mismatch() {
+        let mut assembler = BlobAssembler::new();
+        let id = Uuid::new_v4();
+
+        assembler.handle(start(id, b"ab", 1)).unwrap();
+        assert!(matches!(
+            assembler.handle(chunk(id, 0, b"abc")),
+            Err(BlobError::SizeMismatch {
+                expected: 2,
+                received: 3
+            })
+        ));
+
+        assembler.handle(start(id, b"ab", 1)).unwrap();
+        assembler.handle(chunk(id, 0, b"a")).unwrap();
+        assert!(matches!(
+            assembler.handle(end(id, b"a")),
+            Err(BlobError::SizeMismatch {
+                expected: 2,
+                received: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_blobs_over_max_size() {
+        let mut assembler = BlobAssembler::with_max_size(3);
+        let id = Uuid::new_v4();
+
+        assert!(matches!(
+            assembler.handle(start(id, b"abcd", 1)),
+            Err(BlobError::TooLarge { size: 4, max: 3 })
+        ));
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    #[test]
+    fn abort_discards_transfer() {
+        let mut assembler = BlobAssembler::new();
+        let id = Uuid::new_v4();
+
+        assembler.handle(start(id, b"abcd", 2)).unwrap();
+        assembler.handle(chunk(id, 0, b"ab")).unwrap();
+
+        let abort = BlobMessage::Abort {
+            id,
+            reason: "cancelled".to_string(),
+        };
+        assert!(matches!(
+            assembler.handle(abort),
+            Err(BlobError::Aborted(reason)) if reason == "cancelled"
+        ));
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    #[test]
+    fn interleaved_transfers_are_separate() {
+        let mut assembler = BlobAssembler::new();
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assembler.handle(start(first, b"aabb", 2)).unwrap();
+        assembler.handle(start(second, b"ccdd", 2)).unwrap();
+        assembler.handle(chunk(first, 0, b"aa")).unwrap();
+        assembler.handle(chunk(second, 0, b"cc")).unwrap();
+        assembler.handle(chunk(second, 1, b"dd")).unwrap();
+        assembler.handle(chunk(first, 1, b"bb")).unwrap();
+        assert_eq!(assembler.pending(), 2);
+
+        let blob = assembler.handle(end(second, b"ccdd")).unwrap().unwrap();
+        assert_eq!(blob.data, b"ccdd");
+        let blob = assembler.handle(end(first, b"aabb")).unwrap().unwrap();
+        assert_eq!(blob.data, b"aabb");
+    }
+
+    #[tokio::test]
+    async fn send_blob_round_trips() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let sent = RefCell::new(Vec::new());
+
+        let options = BlobOptions::new()
+            .name("data.bin")
+            .mime_type("application/octet-stream")
+            .chunk_size(300);
+        let id = send_blob(
+            |message| {
+                sent.borrow_mut().push(message);
+                Ok(())
+            },
+            &data,
+            options,
+        )
+        .await
+        .unwrap();
+
+        // Start, four chunks and the end
+        let sent = sent.into_inner();
+        assert_eq!(sent.len(), 6);
+
+        let mut assembler = BlobAssembler::new();
+        let mut blob = None;
+        for message in sent {
+            let message = serde_json::from_value(serde_json::to_value(message).unwrap()).unwrap();
+            blob = assembler.handle(message).unwrap();
+        }
+
+        let blob = blob.unwrap();
+        assert_eq!(blob.id, id);
+        assert_eq!(blob.name.as_deref(), Some("data.bin"));
+        assert_eq!(blob.mime_type.as_deref(), Some("application/octet-stream"));
+        assert_eq!(blob.data, data);
+    }
+
+    fn receive(
+        uploads: &Uploads<u32>,
+        key: u32,
+        message: BlobMessage,
+        aborts: &RefCell<Vec<BlobMessage>>,
+    ) -> Option<Blob> {
+        uploads.receive(key, serde_json::to_value(message).unwrap(), |message| {
+            aborts.borrow_mut().push(message);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn uploads_abort_failed_transfers() {
+        let uploads = Uploads::default();
+        let aborts = RefCell::new(Vec::new());
+        let id = Uuid::new_v4();
+
+        receive(&uploads, 1, start(id, b"ab", 1), &aborts);
+        receive(&uploads, 1, chunk(id, 0, b"ab"), &aborts);
+        assert!(receive(&uploads, 1, end(id, b"ba"), &aborts).is_none());
+
+        let aborts = aborts.into_inner();
+        assert!(
+            matches!(&aborts[..], [BlobMessage::Abort { id: abort_id, .. }] if *abort_id == id)
+        );
+    }
+
+    #[test]
+    fn uploads_do_not_reply_to_sender_aborts() {
+        let uploads = Uploads::default();
+        let aborts = RefCell::new(Vec::new());
+        let id = Uuid::new_v4();
+
+        receive(&uploads, 1, start(id, b"ab", 1), &aborts);
+        let abort = BlobMessage::Abort {
+            id,
+            reason: "cancelled".to_string(),
+        };
+        receive(&uploads, 1, abort, &aborts);
+
+        assert!(aborts.borrow().is_empty());
+        assert!(uploads.senders.lock().is_empty());
+    }
+
+    #[test]
+    fn uploads_keep_senders_separate() {
+        let uploads = Uploads::default();
+        let aborts = RefCell::new(Vec::new());
+        let id = Uuid::new_v4();
+
+        // Same transfer ID from two senders
+        receive(&uploads, 1, start(id, b"ab", 1), &aborts);
+        receive(&uploads, 2, start(id, b"cd", 1), &aborts);
+        receive(&uploads, 1, chunk(id, 0, b"ab"), &aborts);
+        receive(&uploads, 2, chunk(id, 0, b"cd"), &aborts);
+
+        let blob = receive(&uploads, 2, end(id, b"cd"), &aborts).unwrap();
+        assert_eq!(blob.data, b"cd");
+        let blob = receive(&uploads, 1, end(id, b"ab"), &aborts).unwrap();
+        assert_eq!(blob.data, b"ab");
+
+        assert!(aborts.borrow().is_empty());
+        assert!(uploads.senders.lock().is_empty());
+    }
+
+    #[test]
+    fn uploads_evict_idle_senders() {
+        let uploads = Uploads::with_timeout(Duration::from_millis(10));
+        let aborts = RefCell::new(Vec::new());
+
+        receive(&uploads, 1, start(Uuid::new_v4(), b"ab", 1), &aborts);
+        assert_eq!(uploads.senders.lock().len(), 1);
+
+        std::thread::sleep(Duration::from_millis(20));
+
+        // Any message from another sender evicts the stale sender
+        receive(&uploads, 2, start(Uuid::new_v4(), b"ab", 1), &aborts);
+        let senders = uploads.senders.lock();
+        assert_eq!(senders.len(), 1);
+        assert!(senders.contains_key(&2));
+    }
+}
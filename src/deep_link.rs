@@ -0,0 +1,736 @@
+use std::borrow::Cow;
+
+use percent_encoding::{
+    AsciiSet, CONTROLS, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode,
+};
+use serde::{
+    de::{self, DeserializeOwned, IntoDeserializer, value::MapDeserializer},
+    forward_to_deserialize_any,
+};
+use thiserror::Error;
+
+use crate::{
+    protocol::{DeepLinkContext, PluginId},
+    session::PluginSessionHandle,
+};
+
+/// Error from extracting the parameters of a deep link
+#[derive(Debug, Clone, Error)]
+pub enum DeepLinkError {
+    /// Path parameters could not be deserialized
+    #[error("invalid path parameters: {0}")]
+    InvalidParams(String),
+
+    /// Query parameters could not be deserialized
+    #[error("invalid query parameters: {0}")]
+    InvalidQuery(String),
+
+    /// URL is not a deep link for a plugin
+    #[error("invalid deep link url: {0}")]
+    InvalidUrl(String),
+
+    /// Path segment is empty, `.` or `..` which can't be represented
+    #[error("invalid deep link path segment \"{0}\"")]
+    InvalidSegment(String),
+}
+
+/// Scheme used by Tilepad deep links
+const DEEP_LINK_SCHEME: &str = "tilepad://";
+
+/// Host used by Tilepad deep links
+const DEEP_LINK_HOST: &str = "deep-link";
+
+/// Characters encoded within a path segment
+const SEGMENT_ENCODE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Characters encoded within a query key or value
+const QUERY_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Builder for deep link URLs that route back to a plugin
+///
+/// Deep links have the form `tilepad://deep-link/{plugin_id}/{path}?{query}#{fragment}`,
+/// this follows the deep link handling of the Tilepad app which registers the
+/// `tilepad` URL scheme, and forwards URLs with the `deep-link` host to the
+/// plugin named by the first path segment. The plugin receives them as a
+/// [DeepLinkContext] with the `path`, `query` and `fragment` following the
+/// plugin ID. Path segments and query parameters are percent-encoded so they
+/// decode back to the same values in a [DeepLinkRouter]
+///
+/// Empty, `.` and `..` path segments can't be represented as they are dropped
+/// or resolved when the URL is opened, [DeepLinkUrl::path] resolves them and
+/// [DeepLinkUrl::segment] rejects them
+///
+/// ```
+/// use tilepad_plugin_sdk::DeepLinkUrl;
+///
+/// let url = DeepLinkUrl::new("com.example.plugin")
+///     .path("/auth/callback")
+///     .query("state", "a b&c")
+///     .build();
+///
+/// assert_eq!(url, "tilepad://deep-link/com.example.plugin/auth/callback?state=a%20b%26c");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeepLinkUrl {
+    plugin_id: PluginId,
+    segments: Vec<String>,
+    query: Vec<(String, String)>,
+    fragment: Option<String>,
+}
+
+impl DeepLinkUrl {
+    /// Creates a deep link to the root path of the plugin `plugin_id`
+    pub fn new(plugin_id: impl Into<PluginId>) -> Self {
+        Self {
+            plugin_id: plugin_id.into(),
+            segments: Vec::new(),
+            query: Vec::new(),
+            fragment: None,
+        }
+    }
+
+    /// Appends the segments of `path`, split on `/`, to the path
+    ///
+    /// Empty and `.` segments are skipped, `..` removes the previous segment
+    pub fn path(mut self, path: &str) -> Self {
+        for segment in path_segments(path) {
+            match segment {
+                "." => {}
+                ".." => {
+                    self.segments.pop();
+                }
+                segment => self.segments.push(segment.to_string()),
+            }
+        }
+        self
+    }
+
+    /// Appends a single `segment` to the path, the segment may contain `/`
+    ///
+    /// Fails with [DeepLinkError::InvalidSegment] if the segment is
+    /// empty, `.` or `..`
+    pub fn segment(mut self, segment: impl ToString) -> Result<Self, DeepLinkError> {
+        let segment = segment.to_string();
+        if is_dot_or_empty(&segment) {
+            return Err(DeepLinkError::InvalidSegment(segment));
+        }
+
+        self.segments.push(segment);
+        Ok(self)
+    }
+
+    /// Appends a query parameter
+    pub fn query(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.query.push((key.into(), value.to_string()));
+        self
+    }
+
+    /// Sets the fragment
+    pub fn fragment(mut self, fragment: impl Into<String>) -> Self {
+        self.fragment = Some(fragment.into());
+        self
+    }
+
+    /// ID of the plugin the deep link routes to
+    pub fn plugin_id(&self) -> &str {
+        &self.plugin_id
+    }
+
+    /// Decoded path segments
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// Decoded query parameters
+    pub fn query_params(&self) -> &[(String, String)] {
+        &self.query
+    }
+
+    /// Encoded path as received in [DeepLinkContext::path]
+    fn encoded_path(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.segments {
+            path.push('/');
+            path.extend(utf8_percent_encode(segment, SEGMENT_ENCODE));
+        }
+
+        if path.is_empty() {
+            path.push('/');
+        }
+
+        path
+    }
+
+    /// Encoded query as received in [DeepLinkContext::query]
+    fn encoded_query(&self) -> Option<String> {
+        if self.query.is_empty() {
+            return None;
+        }
+
+        let query = self
+            .query
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(key, QUERY_ENCODE),
+                    utf8_percent_encode(value, QUERY_ENCODE)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        Some(query)
+    }
+
+    /// Encoded fragment as received in [DeepLinkContext::fragment]
+    fn encoded_fragment(&self) -> Option<String> {
+        self.fragment
+            .as_deref()
+            .map(|fragment| utf8_percent_encode(fragment, QUERY_ENCODE).to_string())
+    }
+
+    /// Builds the deep link URL
+    pub fn build(&self) -> String {
+        let mut url = format!(
+            "{DEEP_LINK_SCHEME}{DEEP_LINK_HOST}/{}{}",
+            utf8_percent_encode(&self.plugin_id, SEGMENT_ENCODE),
+            self.encoded_path()
+        );
+
+        if self.segments.is_empty() {
+            url.pop();
+        }
+
+        if let Some(query) = self.encoded_query() {
+            url.push('?');
+            url.push_str(&query);
+        }
+
+        if let Some(fragment) = self.encoded_fragment() {
+            url.push('#');
+            url.push_str(&fragment);
+        }
+
+        url
+    }
+
+    /// Creates the [DeepLinkContext] the plugin receives when the deep link is opened
+    pub fn to_context(&self) -> DeepLinkContext {
+        DeepLinkContext {
+            url: self.build(),
+            host: Some(DEEP_LINK_HOST.to_string()),
+            path: self.encoded_path(),
+            query: self.encoded_query(),
+            fragment: self.encoded_fragment(),
+        }
+    }
+
+    /// Parses a deep link `url`, the inverse of [DeepLinkUrl::build]
+    pub fn parse(url: &str) -> Result<Self, DeepLinkError> {
+        let invalid = || DeepLinkError::InvalidUrl(url.to_string());
+
+        let rest = url
+            .strip_prefix(DEEP_LINK_SCHEME)
+            .and_then(|rest| rest.strip_prefix(DEEP_LINK_HOST))
+            .and_then(|rest| rest.strip_prefix('/'))
+            .ok_or_else(invalid)?;
+
+        let (rest, fragment) = match rest.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (rest, None),
+        };
+
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+
+        let mut segments = path.split('/');
+        let plugin_id = segments
+            .next()
+            .filter(|plugin_id| !plugin_id.is_empty())
+            .ok_or_else(invalid)?;
+
+        let segments = segments
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| decode(segment).into_owned())
+            .collect::<Vec<_>>();
+
+        // Dot segments would be resolved when the URL is opened
+        if segments.iter().any(|segment| is_dot_or_empty(segment)) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            plugin_id: decode(plugin_id).into_owned(),
+            segments,
+            query: query.map(parse_query).unwrap_or_default(),
+            fragment: fragment.map(|fragment| decode(fragment).into_owned()),
+        })
+    }
+}
+
+impl std::fmt::Display for DeepLinkUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.build())
+    }
+}
+
+/// Handler for a deep link route with the parameters erased
+type RouteHandler<P> = Box<
+    dyn Fn(
+        &mut P,
+        &PluginSessionHandle,
+        &DeepLinkContext,
+        &Params,
+        &Params,
+    ) -> Result<(), DeepLinkError>,
+>;
+
+/// Router for deep links received by the plugin
+///
+/// Routes are registered in [Plugin::deep_link_routes](crate::Plugin::deep_link_routes)
+/// with patterns matched against [DeepLinkContext::path], segments wrapped
+/// in braces such as `/tiles/{tile_id}/toggle` capture a path parameter.
+/// Path and query parameters are percent-decoded and deserialized into the
+/// typed parameters of the route, use [serde::de::IgnoredAny] or `()`
+/// when a route has no parameters
+///
+/// Deep links that don't match a route, or have parameters that fail to
+/// deserialize, are passed on to [Plugin::on_deep_link](crate::Plugin::on_deep_link)
+pub struct DeepLinkRouter<P> {
+    routes: Vec<(RoutePattern, RouteHandler<P>)>,
+}
+
+impl<P> Default for DeepLinkRouter<P> {
+    fn default() -> Self {
+        Self {
+            routes: Default::default(),
+        }
+    }
+}
+
+impl<P> DeepLinkRouter<P> {
+    /// Registers a `handler` for deep links matching `pattern`
+    ///
+    /// Routes are matched in the order they are registered
+    pub fn route<Path, Query, F>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        Path: DeserializeOwned,
+        Query: DeserializeOwned,
+        F: Fn(&mut P, &PluginSessionHandle, DeepLinkContext, Path, Query) + 'static,
+    {
+        self.routes.push((
+            RoutePattern::parse(pattern),
+            Box::new(move |plugin, session, ctx, path, query| {
+                let path = Path::deserialize(ParamsDeserializer(path))
+                    .map_err(|cause| DeepLinkError::InvalidParams(cause.0))?;
+                let query = Query::deserialize(ParamsDeserializer(query))
+                    .map_err(|cause| DeepLinkError::InvalidQuery(cause.0))?;
+
+                handler(plugin, session, ctx.clone(), path, query);
+                Ok(())
+            }),
+        ));
+        self
+    }
+
+    /// Handles a deep link, returns the deep link back when it was
+    /// not handled by a route
+    pub(crate) fn handle(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        ctx: DeepLinkContext,
+    ) -> Option<DeepLinkContext> {
+        let query = ctx.query.as_deref().map(parse_query).unwrap_or_default();
+
+        for (pattern, handler) in &self.routes {
+            let Some(path) = pattern.matches(&ctx.path) else {
+                continue;
+            };
+
+            return match handler(plugin, session, &ctx, &path, &query) {
+                Ok(()) => None,
+                Err(cause) => {
+                    tracing::warn!(?cause, path = ctx.path, "invalid deep link parameters");
+                    Some(ctx)
+                }
+            };
+        }
+
+        Some(ctx)
+    }
+}
+
+/// Decoded parameter names and values
+type Params = Vec<(String, String)>;
+
+/// Segment of a route pattern
+enum Segment {
+    /// Segment that must match exactly
+    Literal(String),
+    /// Segment captured as a named parameter
+    Param(String),
+}
+
+/// Parsed route pattern
+struct RoutePattern {
+    segments: Vec<Segment>,
+}
+
+impl RoutePattern {
+    fn parse(pattern: &str) -> Self {
+        let segments = path_segments(pattern)
+            .map(|segment| {
+                match segment
+                    .strip_prefix('{')
+                    .and_then(|segment| segment.strip_suffix('}'))
+                {
+                    Some(name) => Segment::Param(name.to_string()),
+                    None => Segment::Literal(decode(segment).into_owned()),
+                }
+            })
+            .collect();
+
+        Self { segments }
+    }
+
+    /// Matches the `path` against the pattern, returns the decoded
+    /// path parameters when the path matches
+    fn matches(&self, path: &str) -> Option<Params> {
+        let mut params = Vec::new();
+        let mut segments = path_segments(path);
+
+        for expected in &self.segments {
+            let segment = decode(segments.next()?);
+
+            match expected {
+                Segment::Literal(literal) => {
+                    if segment != literal.as_str() {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => params.push((name.clone(), segment.into_owned())),
+            }
+        }
+
+        if segments.next().is_some() {
+            return None;
+        }
+
+        Some(params)
+    }
+}
+
+/// Splits a path into its non-empty segments, ignoring
+/// leading, trailing and repeated slashes
+fn path_segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+/// Checks if a decoded path segment is empty or a `.` or `..` segment
+fn is_dot_or_empty(segment: &str) -> bool {
+    matches!(segment, "" | "." | "..")
+}
+
+/// Percent-decodes a path segment
+fn decode(value: &str) -> Cow<'_, str> {
+    percent_decode_str(value).decode_utf8_lossy()
+}
+
+/// Parses a query string into its decoded parameters, `+` is
+/// decoded as a space
+pub(crate) fn parse_query(query: &str) -> Params {
+    let decode = |value: &str| {
+        let value = value.replace('+', " ");
+        percent_decode_str(&value).decode_utf8_lossy().into_owned()
+    };
+
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (decode(key), decode(value)),
+            None => (decode(pair), String::new()),
+        })
+        .collect()
+}
+
+/// Error from deserializing parameters
+#[derive(Debug)]
+struct ParamsError(String);
+
+impl std::fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+impl de::Error for ParamsError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// Deserializer for a set of string parameters as a map
+struct ParamsDeserializer<'a>(&'a Params);
+
+impl<'de> de::Deserializer<'de> for ParamsDeserializer<'de> {
+    type Error = ParamsError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let entries = self
+            .0
+            .iter()
+            .map(|(key, value)| (key.as_str(), ValueDeserializer(value)));
+        visitor.visit_map(MapDeserializer::new(entries))
+    }
+
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Deserializer for a single string parameter value, primitive
+/// types are parsed from the string
+struct ValueDeserializer<'a>(&'a str);
+
+impl<'de> IntoDeserializer<'de, ParamsError> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = ParamsError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::subscription::Subscriptions;
+
+    fn session() -> PluginSessionHandle {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        PluginSessionHandle::new(tx, Subscriptions::default())
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct TileParams {
+        name: String,
+        index: u32,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct StateQuery {
+        state: String,
+    }
+
+    type Received = Vec<(TileParams, StateQuery)>;
+
+    fn router() -> DeepLinkRouter<Received> {
+        let mut router = DeepLinkRouter::default();
+        router.route(
+            "/tiles/{name}/{index}",
+            |received: &mut Received, _, _, path, query| received.push((path, query)),
+        );
+        router
+    }
+
+    #[test]
+    fn build_round_trips_through_parse() {
+        let url = DeepLinkUrl::new("com.example.plugin")
+            .path("/tiles")
+            .segment("a/b ?#%")
+            .unwrap()
+            .segment("\u{1F600}")
+            .unwrap()
+            .query("state", "a b&c=d+e")
+            .query("empty", "")
+            .fragment("top section");
+
+        assert_eq!(DeepLinkUrl::parse(&url.build()).unwrap(), url);
+        assert_eq!(DeepLinkUrl::parse(&url.to_context().url).unwrap(), url);
+    }
+
+    #[test]
+    fn root_url_round_trips() {
+        let url = DeepLinkUrl::new("com.example.plugin");
+        assert_eq!(url.build(), "tilepad://deep-link/com.example.plugin");
+        assert_eq!(url.to_context().path, "/");
+        assert_eq!(DeepLinkUrl::parse(&url.build()).unwrap(), url);
+    }
+
+    #[test]
+    fn context_matches_router_params() {
+        let url = DeepLinkUrl::new("com.example.plugin")
+            .path("/tiles")
+            .segment("a/b c")
+            .unwrap()
+            .segment(7)
+            .unwrap()
+            .query("state", "x&y z");
+
+        let mut received = Received::new();
+        let unhandled = router().handle(&mut received, &session(), url.to_context());
+
+        assert!(unhandled.is_none());
+        assert_eq!(
+            received,
+            [(
+                TileParams {
+                    name: "a/b c".to_string(),
+                    index: 7,
+                },
+                StateQuery {
+                    state: "x&y z".to_string(),
+                },
+            )]
+        );
+    }
+
+    #[test]
+    fn unmatched_context_is_returned() {
+        let mut received = Received::new();
+        let router = router();
+
+        let other_path = DeepLinkUrl::new("plugin")
+            .path("/tiles/a")
+            .query("state", "x");
+        let bad_params = DeepLinkUrl::new("plugin")
+            .path("/tiles/a/not-a-number")
+            .query("state", "x");
+
+        assert!(
+            router
+                .handle(&mut received, &session(), other_path.to_context())
+                .is_some()
+        );
+        assert!(
+            router
+                .handle(&mut received, &session(), bad_params.to_context())
+                .is_some()
+        );
+        assert!(received.is_empty());
+    }
+
+    #[test]
+    fn path_resolves_dot_segments() {
+        let url = DeepLinkUrl::new("plugin").path("/a/./b/../c//d");
+        assert_eq!(url.segments(), ["a", "c", "d"]);
+    }
+
+    #[test]
+    fn segment_rejects_unrepresentable_segments() {
+        for segment in ["", ".", ".."] {
+            assert!(matches!(
+                DeepLinkUrl::new("plugin").segment(segment),
+                Err(DeepLinkError::InvalidSegment(value)) if value == segment
+            ));
+        }
+
+        let url = DeepLinkUrl::new("plugin").segment("...").unwrap();
+        assert_eq!(url.build(), "tilepad://deep-link/plugin/...");
+    }
+
+    #[test]
+    fn parse_rejects_dot_segments() {
+        assert!(DeepLinkUrl::parse("tilepad://deep-link/plugin/a/../b").is_err());
+        assert!(DeepLinkUrl::parse("tilepad://deep-link/plugin/%2E%2E").is_err());
+        assert!(DeepLinkUrl::parse("https://example.com/plugin").is_err());
+    }
+}
@@ -1,10 +1,23 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
 use serde::Serialize;
 
 use crate::{
-    protocol::{ClientPluginMessage, DisplayContext},
+    blob::{BlobId, BlobOptions, send_blob},
+    protocol::{ClientPluginMessage, DeviceId, DisplayContext, ServerPluginMessage, TileId},
     session::{PluginSessionHandle, SessionError},
 };
 
+/// Value of the `display` key in the message a display sends when it has
+/// loaded and is ready to receive messages, the latest display state is
+/// resent in response
+///
+/// ```json
+/// { "display": "Ready" }
+/// ```
+pub const DISPLAY_READY_VALUE: &str = "Ready";
+
 /// Reference to an display that can be
 /// used to send messages
 #[derive(Clone)]
@@ -28,4 +41,178 @@ impl Display {
                 message,
             })
     }
+
+    /// Sends `data` to the display as a chunked blob transfer,
+    /// see [BlobMessage](crate::BlobMessage) for the message format
+    pub async fn send_blob(
+        &self,
+        data: &[u8],
+        options: BlobOptions,
+    ) -> Result<BlobId, SessionError> {
+        send_blob(|message| self.send(message), data, options).await
+    }
+
+    /// Sets the latest state for the display and sends it, the state is
+    /// resent whenever the display reports it is ready, see [PluginSessionHandle::set_display_state]
+    pub fn set_state<M>(&self, state: M) -> Result<(), SessionError>
+    where
+        M: Serialize,
+    {
+        self.session.set_display_state(self.ctx.clone(), state)
+    }
+}
+
+/// Store for the latest state set for each display
+#[derive(Default, Clone)]
+pub(crate) struct DisplayStates {
+    states: Arc<Mutex<HashMap<(DeviceId, TileId), DisplayState>>>,
+}
+
+struct DisplayState {
+    ctx: DisplayContext,
+    state: serde_json::Value,
+}
+
+impl DisplayStates {
+    pub fn set(&self, ctx: DisplayContext, state: serde_json::Value) {
+        self.states
+            .lock()
+            .insert((ctx.device_id, ctx.tile_id), DisplayState { ctx, state });
+    }
+
+    pub fn remove(&self, ctx: &DisplayContext) {
+        self.states.lock().remove(&(ctx.device_id, ctx.tile_id));
+    }
+
+    /// Finds the state that should be resent in response to a message from
+    /// the server, this is when a display reports that it is ready
+    pub fn replay(&self, msg: &ServerPluginMessage) -> Option<(DisplayContext, serde_json::Value)> {
+        let ServerPluginMessage::RecvFromDisplay { ctx, message } = msg else {
+            return None;
+        };
+
+        if !message
+            .get("display")
+            .is_some_and(|value| value == DISPLAY_READY_VALUE)
+        {
+            return None;
+        }
+
+        let states = &*self.states.lock();
+        states
+            .get(&(ctx.device_id, ctx.tile_id))
+            .map(|state| (state.ctx.clone(), state.state.clone()))
+    }
+
+    pub fn clear(&self) {
+        self.states.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::protocol::{TileConfig, TileIcon, TileLabel, TileModel, TilePosition};
+
+    fn display(device_id: DeviceId, tile_id: TileId) -> DisplayContext {
+        DisplayContext {
+            device_id,
+            plugin_id: "plugin".to_string(),
+            action_id: "action".to_string(),
+            tile_id,
+        }
+    }
+
+    fn tile(id: TileId, icon: TileIcon) -> TileModel {
+        TileModel {
+            id,
+            config: TileConfig {
+                icon,
+                label: TileLabel::default(),
+                icon_options: None,
+            },
+            properties: Default::default(),
+            folder_id: Uuid::new_v4(),
+            plugin_id: "plugin".to_string(),
+            action_id: "action".to_string(),
+            position: TilePosition {
+                row: 0,
+                column: 0,
+                row_span: 1,
+                column_span: 1,
+            },
+        }
+    }
+
+    fn display_icon() -> TileIcon {
+        TileIcon::Display {
+            path: "display.html".to_string(),
+        }
+    }
+
+    fn ready(ctx: &DisplayContext) -> ServerPluginMessage {
+        ServerPluginMessage::RecvFromDisplay {
+            ctx: ctx.clone(),
+            message: json!({ "display": DISPLAY_READY_VALUE }),
+        }
+    }
+
+    #[test]
+    fn replays_only_when_ready() {
+        let states = DisplayStates::default();
+        let ctx = display(Uuid::new_v4(), Uuid::new_v4());
+        states.set(ctx.clone(), json!({ "value": 1 }));
+
+        let device_tiles = ServerPluginMessage::DeviceTiles {
+            device_id: ctx.device_id,
+            tiles: vec![tile(ctx.tile_id, display_icon())],
+        };
+        assert!(states.replay(&device_tiles).is_none());
+
+        let other = ServerPluginMessage::RecvFromDisplay {
+            ctx: ctx.clone(),
+            message: json!({ "display": "Other" }),
+        };
+        assert!(states.replay(&other).is_none());
+
+        let (replayed, state) = states.replay(&ready(&ctx)).unwrap();
+        assert_eq!(replayed.tile_id, ctx.tile_id);
+        assert_eq!(state, json!({ "value": 1 }));
+    }
+
+    #[test]
+    fn keeps_state_for_displays_in_other_folders() {
+        let states = DisplayStates::default();
+        let ctx = display(Uuid::new_v4(), Uuid::new_v4());
+        states.set(ctx.clone(), json!({ "value": 1 }));
+
+        // Switching to another folder hides the display tile
+        let other_folder = ServerPluginMessage::DeviceTiles {
+            device_id: ctx.device_id,
+            tiles: vec![tile(Uuid::new_v4(), TileIcon::None)],
+        };
+        assert!(states.replay(&other_folder).is_none());
+
+        // Switching back shows the display again which reports it is ready
+        let same_folder = ServerPluginMessage::DeviceTiles {
+            device_id: ctx.device_id,
+            tiles: vec![tile(ctx.tile_id, display_icon())],
+        };
+        assert!(states.replay(&same_folder).is_none());
+
+        let (_, state) = states.replay(&ready(&ctx)).unwrap();
+        assert_eq!(state, json!({ "value": 1 }));
+    }
+
+    #[test]
+    fn removed_state_is_not_replayed() {
+        let states = DisplayStates::default();
+        let ctx = display(Uuid::new_v4(), Uuid::new_v4());
+        states.set(ctx.clone(), json!(null));
+        states.remove(&ctx);
+        assert!(states.replay(&ready(&ctx)).is_none());
+    }
 }
@@ -0,0 +1,350 @@
+use std::{sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::{
+    runtime::Handle,
+    time::{Instant, sleep_until},
+};
+
+use crate::{display::Display, protocol::ClientPluginMessage, session::SessionError};
+
+/// Rate limited stream of frames sent to a display
+///
+/// Frames pushed faster than the maximum rate are coalesced, only the
+/// latest frame is delivered once the display is ready for another frame
+/// and the stale frames in between are dropped
+///
+/// Created using [Display::stream], delayed frames are sent from a task
+/// spawned on the tokio runtime the stream was created on. When the stream
+/// is created outside of a runtime the runtime current when a frame is
+/// delayed is used instead, [DisplayStream::push] returns
+/// [SessionError::NoRuntime] when there is neither
+#[derive(Clone)]
+pub struct DisplayStream {
+    inner: Arc<DisplayStreamInner>,
+}
+
+struct DisplayStreamInner {
+    /// Display the frames are sent to
+    display: Display,
+    /// Minimum time between frames
+    interval: Duration,
+    /// Runtime the stream was created on, used to send delayed frames
+    runtime: Option<Handle>,
+    /// Current stream state
+    state: Mutex<DisplayStreamState>,
+}
+
+#[derive(Default)]
+struct DisplayStreamState {
+    /// When the last frame was sent
+    last_sent: Option<Instant>,
+    /// Latest frame waiting to be sent
+    pending: Option<serde_json::Value>,
+    /// Whether a task is waiting to send the pending frame
+    flush_scheduled: bool,
+    /// Total number of frames sent
+    sent: u64,
+    /// Total number of frames dropped
+    skipped: u64,
+}
+
+/// Statistics for a [DisplayStream]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayStreamStats {
+    /// Number of frames sent to the display
+    pub sent: u64,
+    /// Number of frames dropped because a newer frame replaced them
+    pub skipped: u64,
+}
+
+impl Display {
+    /// Creates a stream for sending frequent updates to the display that
+    /// sends at most `max_rate` frames per second
+    ///
+    /// Rates below [DisplayStream::MIN_RATE] are raised to the minimum
+    pub fn stream(&self, max_rate: f64) -> DisplayStream {
+        let interval = Duration::from_secs_f64(1.0 / max_rate.max(DisplayStream::MIN_RATE));
+
+        DisplayStream {
+            inner: Arc::new(DisplayStreamInner {
+                display: self.clone(),
+                interval,
+                runtime: Handle::try_current().ok(),
+                state: Default::default(),
+            }),
+        }
+    }
+}
+
+impl DisplayStream {
+    /// Minimum frames per second for a stream
+    pub const MIN_RATE: f64 = 0.001;
+
+    /// Pushes a new `frame` to the stream
+    ///
+    /// The frame is sent immediately when the rate allows, otherwise it
+    /// replaces any pending frame and is sent once the rate allows
+    pub fn push<M>(&self, frame: M) -> Result<(), SessionError>
+    where
+        M: Serialize,
+    {
+        let frame = serde_json::to_value(frame)?;
+        let state = &mut *self.inner.state.lock();
+        let now = Instant::now();
+
+        let ready = state
+            .last_sent
+            .is_none_or(|last_sent| now.duration_since(last_sent) >= self.inner.interval);
+
+        if ready && state.pending.is_none() {
+            state.last_sent = Some(now);
+            state.sent += 1;
+            return self.inner.send(frame);
+        }
+
+        if !state.flush_scheduled {
+            let runtime = match &self.inner.runtime {
+                Some(runtime) => runtime.clone(),
+                None => Handle::try_current().map_err(|_| SessionError::NoRuntime)?,
+            };
+
+            let deadline = state
+                .last_sent
+                .map(|last_sent| last_sent + self.inner.interval)
+                .unwrap_or(now);
+
+            state.flush_scheduled = true;
+            runtime.spawn(self.inner.clone().flush_at(deadline));
+        }
+
+        if state.pending.replace(frame).is_some() {
+            state.skipped += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Sends the pending frame immediately ignoring the rate limit
+    pub fn flush(&self) -> Result<(), SessionError> {
+        self.inner.flush()
+    }
+
+    /// Number of frames that were dropped because a newer frame replaced them
+    pub fn skipped(&self) -> u64 {
+        self.inner.state.lock().skipped
+    }
+
+    /// Statistics for the frames sent and dropped by the stream
+    pub fn stats(&self) -> DisplayStreamStats {
+        let state = &*self.inner.state.lock();
+        DisplayStreamStats {
+            sent: state.sent,
+            skipped: state.skipped,
+        }
+    }
+
+    /// Display the stream sends to
+    pub fn display(&self) -> &Display {
+        &self.inner.display
+    }
+}
+
+impl DisplayStreamInner {
+    fn send(&self, frame: serde_json::Value) -> Result<(), SessionError> {
+        self.display
+            .session
+            .send_message(ClientPluginMessage::SendToDisplay {
+                ctx: self.display.ctx.clone(),
+                message: frame,
+            })
+    }
+
+    /// Waits until `deadline` then sends the pending frame
+    ///
+    /// The deadline is extended when a frame was sent by [DisplayStream::flush]
+    /// in the meantime so the pending frame still respects the interval
+    async fn flush_at(self: Arc<Self>, mut deadline: Instant) {
+        loop {
+            sleep_until(deadline).await;
+
+            let state = &mut *self.state.lock();
+            let next = state
+                .last_sent
+                .map(|last_sent| last_sent + self.interval)
+                .filter(|next| *next > deadline && state.pending.is_some());
+
+            match next {
+                Some(next) => deadline = next,
+                None => {
+                    state.flush_scheduled = false;
+                    break;
+                }
+            }
+        }
+
+        if let Err(cause) = self.flush() {
+            tracing::error!(?cause, "failed to send display stream frame");
+        }
+    }
+
+    fn flush(&self) -> Result<(), SessionError> {
+        let state = &mut *self.state.lock();
+        let Some(frame) = state.pending.take() else {
+            return Ok(());
+        };
+
+        state.last_sent = Some(Instant::now());
+        state.sent += 1;
+        self.send(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        protocol::DisplayContext,
+        session::PluginSessionHandle,
+        subscription::Subscriptions,
+        ws::{WsMessage, WsRx},
+    };
+
+    fn display() -> (Display, WsRx) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let display = Display {
+            session: PluginSessionHandle::new(tx, Subscriptions::default()),
+            ctx: DisplayContext {
+                device_id: Uuid::new_v4(),
+                plugin_id: "plugin".to_string(),
+                action_id: "action".to_string(),
+                tile_id: Uuid::new_v4(),
+            },
+        };
+        (display, rx)
+    }
+
+    /// Drains the frames sent to the display
+    fn frames(rx: &mut WsRx) -> Vec<serde_json::Value> {
+        let mut frames = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            let WsMessage::Text(text) = message else {
+                continue;
+            };
+            let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+            frames.push(message["message"].clone());
+        }
+        frames
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn coalesces_frames_within_interval() {
+        let (display, mut rx) = display();
+        let stream = display.stream(10.0);
+
+        stream.push(1).unwrap();
+        stream.push(2).unwrap();
+        stream.push(3).unwrap();
+        assert_eq!(frames(&mut rx), [1]);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(frames(&mut rx).is_empty());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(frames(&mut rx), [3]);
+        assert_eq!(
+            stream.stats(),
+            DisplayStreamStats {
+                sent: 2,
+                skipped: 1
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sends_immediately_once_interval_passed() {
+        let (display, mut rx) = display();
+        let stream = display.stream(10.0);
+
+        stream.push(1).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        stream.push(2).unwrap();
+
+        assert_eq!(frames(&mut rx), [1, 2]);
+        assert_eq!(stream.skipped(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn flush_ignores_rate_limit() {
+        let (display, mut rx) = display();
+        let stream = display.stream(10.0);
+
+        stream.push(1).unwrap();
+        stream.push(2).unwrap();
+        stream.flush().unwrap();
+        assert_eq!(frames(&mut rx), [1, 2]);
+
+        // Scheduled flush has nothing left to send
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(frames(&mut rx).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn flush_resets_interval_for_scheduled_frames() {
+        let (display, mut rx) = display();
+        let stream = display.stream(10.0);
+
+        stream.push(1).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.push(2).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        stream.flush().unwrap();
+        assert_eq!(frames(&mut rx), [1, 2]);
+
+        // Frame scheduled before the flush waits a full interval after it
+        stream.push(3).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(frames(&mut rx).is_empty());
+
+        tokio::time::sleep(Duration::from_millis(90)).await;
+        assert_eq!(frames(&mut rx), [3]);
+    }
+
+    #[test]
+    fn uses_runtime_stream_was_created_on() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let (display, mut rx) = display();
+        let stream = runtime.block_on(async { display.stream(100.0) });
+
+        stream.push(1).unwrap();
+        stream.push(2).unwrap();
+
+        runtime.block_on(async { tokio::time::sleep(Duration::from_millis(20)).await });
+        assert_eq!(frames(&mut rx), [1, 2]);
+    }
+
+    #[test]
+    fn delayed_frame_without_runtime_is_an_error() {
+        let (display, mut rx) = display();
+        let stream = display.stream(10.0);
+
+        stream.push(1).unwrap();
+        assert!(matches!(stream.push(2), Err(SessionError::NoRuntime)));
+        assert_eq!(frames(&mut rx), [1]);
+        assert_eq!(
+            stream.stats(),
+            DisplayStreamStats {
+                sent: 1,
+                skipped: 0
+            }
+        );
+    }
+}
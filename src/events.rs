@@ -0,0 +1,225 @@
+use futures_util::{Stream, StreamExt, stream};
+use tokio::sync::broadcast;
+
+use crate::{
+    host::HostInfo,
+    protocol::{
+        DeepLinkContext, DeviceId, DisplayContext, InspectorContext, PluginId, ServerPluginMessage,
+        TileId, TileInteractionContext, TileModel,
+    },
+};
+
+/// Event received from the server, see [PluginSessionHandle::events](crate::PluginSessionHandle::events)
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum PluginEvent {
+    /// Plugin has registered with the server
+    Registered { plugin_id: PluginId, host: HostInfo },
+
+    /// Plugin properties were received
+    Properties { properties: serde_json::Value },
+
+    /// Tile was clicked on a device
+    TileClicked {
+        ctx: TileInteractionContext,
+        properties: serde_json::Value,
+    },
+
+    /// Message was received from an inspector
+    RecvFromInspector {
+        ctx: InspectorContext,
+        message: serde_json::Value,
+    },
+
+    /// Message was received from a display
+    RecvFromDisplay {
+        ctx: DisplayContext,
+        message: serde_json::Value,
+    },
+
+    /// Inspector was opened
+    InspectorOpen { ctx: InspectorContext },
+
+    /// Inspector was closed
+    InspectorClose { ctx: InspectorContext },
+
+    /// Deep link was received for the plugin
+    DeepLink { ctx: DeepLinkContext },
+
+    /// Properties were received for a tile
+    TileProperties {
+        tile_id: TileId,
+        properties: serde_json::Value,
+    },
+
+    /// Tiles visible on a device have changed
+    DeviceTiles {
+        device_id: DeviceId,
+        tiles: Vec<TileModel>,
+    },
+
+    /// Tiles that are currently visible
+    VisibleTiles { tiles: Vec<TileModel> },
+
+    /// Host acknowledged a request
+    Ack { request_id: u64 },
+
+    /// Host rejected a request, see [SessionError::Rejected](crate::SessionError::Rejected)
+    Error {
+        request_id: Option<u64>,
+        code: String,
+        message: String,
+    },
+
+    /// Message with a type not known to this version of the SDK,
+    /// see [Plugin::on_unknown_message](crate::Plugin::on_unknown_message)
+    Unknown {
+        message_type: String,
+        message: serde_json::Value,
+    },
+}
+
+impl From<ServerPluginMessage> for PluginEvent {
+    fn from(msg: ServerPluginMessage) -> Self {
+        match msg {
+            ServerPluginMessage::Registered { plugin_id, host } => {
+                PluginEvent::Registered { plugin_id, host }
+            }
+            ServerPluginMessage::Properties { properties } => {
+                PluginEvent::Properties { properties }
+            }
+            ServerPluginMessage::TileClicked { ctx, properties } => {
+                PluginEvent::TileClicked { ctx, properties }
+            }
+            ServerPluginMessage::RecvFromInspector { ctx, message } => {
+                PluginEvent::RecvFromInspector { ctx, message }
+            }
+            ServerPluginMessage::RecvFromDisplay { ctx, message } => {
+                PluginEvent::RecvFromDisplay { ctx, message }
+            }
+            ServerPluginMessage::InspectorOpen { ctx } => PluginEvent::InspectorOpen { ctx },
+            ServerPluginMessage::InspectorClose { ctx } => PluginEvent::InspectorClose { ctx },
+            ServerPluginMessage::DeepLink { ctx } => PluginEvent::DeepLink { ctx },
+            ServerPluginMessage::TileProperties {
+                tile_id,
+                properties,
+            } => PluginEvent::TileProperties {
+                tile_id,
+                properties,
+            },
+            ServerPluginMessage::DeviceTiles { device_id, tiles } => {
+                PluginEvent::DeviceTiles { device_id, tiles }
+            }
+            ServerPluginMessage::VisibleTiles { tiles } => PluginEvent::VisibleTiles { tiles },
+            ServerPluginMessage::Ack { request_id } => PluginEvent::Ack { request_id },
+            ServerPluginMessage::Error {
+                request_id,
+                code,
+                message,
+            } => PluginEvent::Error {
+                request_id,
+                code,
+                message,
+            },
+            ServerPluginMessage::Unknown {
+                message_type,
+                message,
+            } => PluginEvent::Unknown {
+                message_type,
+                message,
+            },
+        }
+    }
+}
+
+/// Creates a stream of the events received by `rx`, events missed
+/// by a consumer that falls too far behind are skipped
+pub(crate) fn event_stream(
+    rx: broadcast::Receiver<PluginEvent>,
+) -> impl Stream<Item = PluginEvent> + Send + 'static {
+    stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some((event, rx)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "event stream lagged behind, skipped events");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ack(request_id: u64) -> PluginEvent {
+        ServerPluginMessage::Ack { request_id }.into()
+    }
+
+    /// Request IDs of the events remaining in `stream`
+    async fn request_ids(stream: impl Stream<Item = PluginEvent>) -> Vec<u64> {
+        stream
+            .map(|event| match event {
+                PluginEvent::Ack { request_id } => request_id,
+                event => panic!("unexpected event {event:?}"),
+            })
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn delivers_events_in_order() {
+        let (tx, rx) = broadcast::channel(4);
+        let stream = event_stream(rx);
+
+        for request_id in 1..=3 {
+            tx.send(ack(request_id)).unwrap();
+        }
+        drop(tx);
+
+        assert_eq!(request_ids(stream).await, [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn skips_missed_events_when_lagging() {
+        let (tx, rx) = broadcast::channel(2);
+        let stream = event_stream(rx);
+
+        for request_id in 1..=5 {
+            tx.send(ack(request_id)).unwrap();
+        }
+        drop(tx);
+
+        assert_eq!(request_ids(stream).await, [4, 5]);
+    }
+
+    #[tokio::test]
+    async fn ends_when_sender_closes() {
+        let (tx, rx) = broadcast::channel(2);
+        let mut stream = event_stream(rx);
+
+        tx.send(ack(1)).unwrap();
+        assert!(matches!(
+            stream.next().await,
+            Some(PluginEvent::Ack { request_id: 1 })
+        ));
+
+        drop(tx);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn converts_unknown_messages() {
+        let event = PluginEvent::from(ServerPluginMessage::Unknown {
+            message_type: "Gradient".to_string(),
+            message: serde_json::json!({ "type": "Gradient" }),
+        });
+        assert!(matches!(
+            event,
+            PluginEvent::Unknown { message_type, .. } if message_type == "Gradient"
+        ));
+    }
+}
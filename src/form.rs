@@ -0,0 +1,436 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    inspector::Inspector, protocol::ActionId, rpc::RpcError, session::PluginSessionHandle,
+};
+
+/// Description of the settings form for an action that a generic
+/// inspector page can render
+///
+/// Schemas are registered with [InspectorRouter::form](crate::InspectorRouter::form)
+/// and served to the inspector through the [FormSchema::SCHEMA_METHOD] and
+/// [FormSchema::OPTIONS_METHOD] RPC methods, see [RpcMessage](crate::RpcMessage)
+///
+/// ```json
+/// { "rpc": "Request", "id": 1, "method": "form.schema" }
+/// { "rpc": "Request", "id": 2, "method": "form.options", "params": { "source": "devices" } }
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FormSchema {
+    /// Fields of the form in display order
+    pub fields: Vec<FormField>,
+}
+
+impl FormSchema {
+    /// RPC method returning the [FormSchema] for the action of the inspector
+    pub const SCHEMA_METHOD: &str = "form.schema";
+
+    /// RPC method returning the [SelectOption]s for a [FieldKind::DynamicSelect]
+    pub const OPTIONS_METHOD: &str = "form.options";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a field to the form
+    pub fn field(mut self, field: FormField) -> Self {
+        self.fields.push(field);
+        self
+    }
+}
+
+/// Single field within a [FormSchema]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormField {
+    /// Key of the property the field edits
+    pub key: String,
+    /// Label displayed for the field
+    pub label: String,
+    /// Help text displayed with the field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Value used when the property is not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    /// Type of input for the field
+    #[serde(flatten)]
+    pub kind: FieldKind,
+}
+
+/// Type of input for a [FormField]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum FieldKind {
+    /// Text input
+    Text {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        placeholder: Option<String>,
+        #[serde(default)]
+        multiline: bool,
+    },
+
+    /// Select from a fixed list of options
+    Select { options: Vec<SelectOption> },
+
+    /// Select from a list of options fetched from the plugin, the
+    /// `source` is passed to the [FormSchema::OPTIONS_METHOD] method
+    DynamicSelect { source: String },
+
+    /// Boolean toggle
+    Toggle,
+
+    /// Numeric slider
+    Slider { min: f64, max: f64, step: f64 },
+
+    /// Color picker
+    Color,
+}
+
+/// Option for a select field
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectOption {
+    /// Value stored in the property when selected
+    pub value: serde_json::Value,
+    /// Label displayed for the option
+    pub label: String,
+}
+
+impl SelectOption {
+    pub fn new(value: impl Into<serde_json::Value>, label: impl Into<String>) -> Self {
+        Self {
+            value: value.into(),
+            label: label.into(),
+        }
+    }
+}
+
+impl FormField {
+    pub fn new(key: impl Into<String>, label: impl Into<String>, kind: FieldKind) -> Self {
+        Self {
+            key: key.into(),
+            label: label.into(),
+            description: None,
+            default: None,
+            kind,
+        }
+    }
+
+    pub fn text(key: impl Into<String>, label: impl Into<String>) -> Self {
+        Self::new(
+            key,
+            label,
+            FieldKind::Text {
+                placeholder: None,
+                multiline: false,
+            },
+        )
+    }
+
+    pub fn select(
+        key: impl Into<String>,
+        label: impl Into<String>,
+        options: Vec<SelectOption>,
+    ) -> Self {
+        Self::new(key, label, FieldKind::Select { options })
+    }
+
+    pub fn dynamic_select(
+        key: impl Into<String>,
+        label: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            key,
+            label,
+            FieldKind::DynamicSelect {
+                source: source.into(),
+            },
+        )
+    }
+
+    pub fn toggle(key: impl Into<String>, label: impl Into<String>) -> Self {
+        Self::new(key, label, FieldKind::Toggle)
+    }
+
+    pub fn slider(
+        key: impl Into<String>,
+        label: impl Into<String>,
+        min: f64,
+        max: f64,
+        step: f64,
+    ) -> Self {
+        Self::new(key, label, FieldKind::Slider { min, max, step })
+    }
+
+    pub fn color(key: impl Into<String>, label: impl Into<String>) -> Self {
+        Self::new(key, label, FieldKind::Color)
+    }
+
+    /// Sets the help text for the field
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets the value used when the property is not set
+    pub fn default_value(mut self, default: impl Into<serde_json::Value>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+}
+
+/// Parameters for the [FormSchema::OPTIONS_METHOD] method
+#[derive(Deserialize)]
+struct OptionsParams {
+    source: String,
+}
+
+/// Handler providing the options for a [FieldKind::DynamicSelect]
+type OptionsHandler<P> =
+    Box<dyn Fn(&mut P, &PluginSessionHandle, &Inspector) -> Result<Vec<SelectOption>, RpcError>>;
+
+/// Form schemas and option sources served to inspectors
+pub(crate) struct FormRoutes<P> {
+    /// Form schemas for each action
+    schemas: HashMap<ActionId, FormSchema>,
+    /// Handlers for dynamic select options
+    options: HashMap<String, OptionsHandler<P>>,
+}
+
+impl<P> Default for FormRoutes<P> {
+    fn default() -> Self {
+        Self {
+            schemas: Default::default(),
+            options: Default::default(),
+        }
+    }
+}
+
+impl<P> FormRoutes<P> {
+    pub fn add_schema(&mut self, action_id: ActionId, schema: FormSchema) {
+        self.schemas.insert(action_id, schema);
+    }
+
+    pub fn add_options<F>(&mut self, source: String, handler: F)
+    where
+        F: Fn(&mut P, &PluginSessionHandle, &Inspector) -> Result<Vec<SelectOption>, RpcError>
+            + 'static,
+    {
+        self.options.insert(source, Box::new(handler));
+    }
+
+    /// Checks whether no schemas or option sources are registered
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty() && self.options.is_empty()
+    }
+
+    /// Handles a form RPC request, returns [None] when the
+    /// method is not a form method
+    pub fn handle(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        inspector: &Inspector,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Option<Result<serde_json::Value, RpcError>> {
+        let outcome = match method {
+            FormSchema::SCHEMA_METHOD => self.schema(inspector),
+            FormSchema::OPTIONS_METHOD => self.options(plugin, session, inspector, params),
+            _ => return None,
+        };
+
+        Some(outcome)
+    }
+
+    fn schema(&self, inspector: &Inspector) -> Result<serde_json::Value, RpcError> {
+        let action_id = &inspector.ctx.action_id;
+        let schema = self.schemas.get(action_id).ok_or_else(|| {
+            RpcError::new(
+                RpcError::NOT_FOUND,
+                format!("no form schema for action {action_id}"),
+            )
+        })?;
+
+        serde_json::to_value(schema).map_err(|cause| RpcError::internal(cause.to_string()))
+    }
+
+    fn options(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        inspector: &Inspector,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let params: OptionsParams =
+            serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+
+        let handler = self.options.get(&params.source).ok_or_else(|| {
+            RpcError::new(
+                RpcError::NOT_FOUND,
+                format!("unknown options source {}", params.source),
+            )
+        })?;
+
+        let options = handler(plugin, session, inspector)?;
+        serde_json::to_value(options).map_err(|cause| RpcError::internal(cause.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{protocol::InspectorContext, subscription::Subscriptions};
+
+    fn inspector(action_id: &str) -> Inspector {
+        let (tx, _) = mpsc::unbounded_channel();
+        Inspector {
+            session: PluginSessionHandle::new(tx, Subscriptions::default()),
+            ctx: InspectorContext {
+                profile_id: Uuid::new_v4(),
+                folder_id: Uuid::new_v4(),
+                plugin_id: "plugin".to_string(),
+                action_id: action_id.to_string(),
+                tile_id: Uuid::new_v4(),
+            },
+        }
+    }
+
+    fn schema() -> FormSchema {
+        FormSchema::new()
+            .field(
+                FormField::text("label", "Label")
+                    .description("Text shown on the tile")
+                    .default_value("CPU"),
+            )
+            .field(FormField::select(
+                "unit",
+                "Unit",
+                vec![SelectOption::new("c", "Celsius")],
+            ))
+            .field(FormField::dynamic_select("device", "Device", "devices"))
+            .field(FormField::slider("threshold", "Threshold", 0.0, 100.0, 5.0))
+            .field(FormField::toggle("enabled", "Enabled"))
+    }
+
+    fn routes() -> FormRoutes<()> {
+        let mut routes = FormRoutes::default();
+        routes.add_schema("action".to_string(), schema());
+        routes.add_options("devices".to_string(), |_, _, _| {
+            Ok(vec![SelectOption::new(1, "Device 1")])
+        });
+        routes
+    }
+
+    fn handle(
+        routes: &FormRoutes<()>,
+        inspector: &Inspector,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Option<Result<serde_json::Value, RpcError>> {
+        routes.handle(&mut (), &inspector.session, inspector, method, params)
+    }
+
+    #[test]
+    fn schema_serialization() {
+        assert_eq!(
+            serde_json::to_value(schema()).unwrap(),
+            json!({
+                "fields": [
+                    {
+                        "key": "label",
+                        "label": "Label",
+                        "description": "Text shown on the tile",
+                        "default": "CPU",
+                        "kind": "Text",
+                        "multiline": false
+                    },
+                    {
+                        "key": "unit",
+                        "label": "Unit",
+                        "kind": "Select",
+                        "options": [{ "value": "c", "label": "Celsius" }]
+                    },
+                    { "key": "device", "label": "Device", "kind": "DynamicSelect", "source": "devices" },
+                    {
+                        "key": "threshold",
+                        "label": "Threshold",
+                        "kind": "Slider",
+                        "min": 0.0,
+                        "max": 100.0,
+                        "step": 5.0
+                    },
+                    { "key": "enabled", "label": "Enabled", "kind": "Toggle" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn serves_schema_for_inspector_action() {
+        let routes = routes();
+
+        let schema = handle(
+            &routes,
+            &inspector("action"),
+            FormSchema::SCHEMA_METHOD,
+            json!(null),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(schema["fields"].as_array().unwrap().len(), 5);
+
+        let error = handle(
+            &routes,
+            &inspector("other"),
+            FormSchema::SCHEMA_METHOD,
+            json!(null),
+        )
+        .unwrap()
+        .unwrap_err();
+        assert_eq!(error.code, RpcError::NOT_FOUND);
+    }
+
+    #[test]
+    fn serves_options_for_source() {
+        let routes = routes();
+        let inspector = inspector("action");
+
+        let options = handle(
+            &routes,
+            &inspector,
+            FormSchema::OPTIONS_METHOD,
+            json!({ "source": "devices" }),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(options, json!([{ "value": 1, "label": "Device 1" }]));
+
+        let error = handle(
+            &routes,
+            &inspector,
+            FormSchema::OPTIONS_METHOD,
+            json!({ "source": "profiles" }),
+        )
+        .unwrap()
+        .unwrap_err();
+        assert_eq!(error.code, RpcError::NOT_FOUND);
+
+        let error = handle(&routes, &inspector, FormSchema::OPTIONS_METHOD, json!({}))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error.code, RpcError::INVALID_PARAMS);
+    }
+
+    #[test]
+    fn ignores_other_methods() {
+        let routes = routes();
+        assert!(handle(&routes, &inspector("action"), "getDevices", json!(null)).is_none());
+    }
+}
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+/// Name of the SDK sent to the host when registering
+pub const SDK_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Version of the SDK sent to the host when registering
+pub const SDK_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Version of the plugin protocol supported by the SDK
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Details about the SDK sent to the host when registering
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SdkInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+}
+
+impl SdkInfo {
+    pub fn current() -> Self {
+        Self {
+            name: SDK_NAME,
+            version: SDK_VERSION,
+        }
+    }
+}
+
+/// Details about the host received when the plugin is registered,
+/// see [PluginSessionHandle::host_info](crate::PluginSessionHandle::host_info)
+///
+/// Hosts that predate capability negotiation don't send these details,
+/// they are treated as having no capabilities
+///
+/// Only the operations that need one of the capabilities below check for
+/// it and return [SessionError::Unsupported](crate::SessionError::Unsupported),
+/// these are [PluginSessionHandle::set_tile_config](crate::PluginSessionHandle::set_tile_config)
+/// and [PluginSessionHandle::with_ack](crate::PluginSessionHandle::with_ack).
+/// Other messages are part of the base protocol and are sent without checking
+/// the host. Helpers that rely on messages from newer hosts, such as the device
+/// tiles used by [PluginSessionHandle::broadcast_to_displays](crate::PluginSessionHandle::broadcast_to_displays),
+/// find nothing when the host does not send them
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct HostInfo {
+    /// Version of the Tilepad host
+    pub host_version: Option<String>,
+    /// Version of the plugin protocol used by the host
+    pub protocol_version: Option<u32>,
+    /// Optional features supported by the host
+    pub capabilities: Vec<String>,
+}
+
+impl HostInfo {
+    /// Host accepts updating the icon, icon options and label of a tile
+    /// in a single message, see [PluginSessionHandle::set_tile_config](crate::PluginSessionHandle::set_tile_config)
+    pub const TILE_CONFIG: &str = "tile_config";
+
+    /// Host acknowledges requests sent with a request ID, see
+    /// [PluginSessionHandle::with_ack](crate::PluginSessionHandle::with_ack)
+    pub const ACKNOWLEDGEMENTS: &str = "acknowledgements";
+
+    /// Checks if the host supports the `capability`
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|supported| supported == capability)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parses_host_details() {
+        let host: HostInfo = serde_json::from_value(json!({
+            "host_version": "0.9.0",
+            "protocol_version": 1,
+            "capabilities": [HostInfo::TILE_CONFIG, "future_feature"]
+        }))
+        .unwrap();
+
+        assert_eq!(host.host_version.as_deref(), Some("0.9.0"));
+        assert_eq!(host.protocol_version, Some(1));
+        assert!(host.supports(HostInfo::TILE_CONFIG));
+        assert!(host.supports("future_feature"));
+        assert!(!host.supports(HostInfo::ACKNOWLEDGEMENTS));
+    }
+
+    #[test]
+    fn older_hosts_have_no_capabilities() {
+        let host: HostInfo = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(host, HostInfo::default());
+        assert!(!host.supports(HostInfo::TILE_CONFIG));
+    }
+
+    #[test]
+    fn sdk_details_are_sent_when_registering() {
+        assert_eq!(
+            serde_json::to_value(SdkInfo::current()).unwrap(),
+            json!({ "name": SDK_NAME, "version": SDK_VERSION })
+        );
+    }
+}
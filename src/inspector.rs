@@ -1,10 +1,41 @@
-use serde::Serialize;
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+
+use parking_lot::Mutex;
+use serde::{Serialize, de::DeserializeOwned};
 
 use crate::{
-    protocol::{ClientPluginMessage, InspectorContext},
+    blob::{BlobId, BlobOptions, send_blob},
+    protocol::{ActionId, ClientPluginMessage, InspectorContext, TileId},
+    rpc::RpcError,
     session::{PluginSessionHandle, SessionError},
 };
 
+/// Action with a fixed vocabulary of messages exchanged with its inspector
+///
+/// Register the action with [InspectorRouter::action](crate::InspectorRouter::action)
+/// to receive deserialized messages from its inspectors
+pub trait InspectorAction: 'static {
+    /// ID of the action within the plugin
+    const ACTION_ID: &'static str;
+
+    /// Messages received from the inspector
+    type InspectorIn: DeserializeOwned;
+
+    /// Messages sent to the inspector
+    type InspectorOut: Serialize;
+}
+
+/// Message sent to the inspector when it sends a message that
+/// could not be understood
+///
+/// ```json
+/// { "error": { "code": "InvalidMessage", "message": "unknown variant `Foo`" } }
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct InspectorErrorMessage {
+    pub error: RpcError,
+}
+
 /// Reference to an inspector window that can be
 /// used to send messages
 #[derive(Clone)]
@@ -28,4 +59,112 @@ impl Inspector {
                 message,
             })
     }
+
+    /// Sends `data` to the inspector window as a chunked blob transfer,
+    /// see [BlobMessage](crate::BlobMessage) for the message format
+    pub async fn send_blob(
+        &self,
+        data: &[u8],
+        options: BlobOptions,
+    ) -> Result<BlobId, SessionError> {
+        send_blob(|message| self.send(message), data, options).await
+    }
+
+    /// Calls the RPC `method` on the inspector window waiting
+    /// for the response, see [PluginSessionHandle::call_inspector]
+    pub async fn call<Params, R>(
+        &self,
+        method: impl Into<String>,
+        params: Params,
+    ) -> Result<R, SessionError>
+    where
+        Params: Serialize,
+        R: DeserializeOwned,
+    {
+        self.session
+            .call_inspector(self.ctx.clone(), method, params)
+            .await
+    }
+}
+
+/// Reference to an inspector window for an [InspectorAction] that
+/// only accepts the messages declared by the action
+pub struct TypedInspector<A> {
+    inspector: Inspector,
+    _action: PhantomData<fn() -> A>,
+}
+
+impl<A> Clone for TypedInspector<A> {
+    fn clone(&self) -> Self {
+        Self {
+            inspector: self.inspector.clone(),
+            _action: PhantomData,
+        }
+    }
+}
+
+impl<A> TypedInspector<A>
+where
+    A: InspectorAction,
+{
+    /// Wraps an `inspector` for the action `A`, returns [None]
+    /// when the inspector belongs to a different action
+    pub fn new(inspector: Inspector) -> Option<Self> {
+        if inspector.ctx.action_id != A::ACTION_ID {
+            return None;
+        }
+
+        Some(Self {
+            inspector,
+            _action: PhantomData,
+        })
+    }
+
+    /// Send a message `msg` to the inspector window
+    pub fn send(&self, msg: A::InspectorOut) -> Result<(), SessionError> {
+        self.inspector.send(msg)
+    }
+
+    /// Context data for the inspector
+    pub fn ctx(&self) -> &InspectorContext {
+        &self.inspector.ctx
+    }
+
+    /// Untyped inspector reference
+    pub fn inspector(&self) -> &Inspector {
+        &self.inspector
+    }
+
+    /// ID of the action
+    pub fn action_id() -> ActionId {
+        A::ACTION_ID.to_string()
+    }
+}
+
+/// Store for the inspectors that are currently open
+#[derive(Default, Clone)]
+pub(crate) struct OpenInspectors {
+    inspectors: Arc<Mutex<HashMap<TileId, InspectorContext>>>,
+}
+
+impl OpenInspectors {
+    pub fn open(&self, ctx: InspectorContext) {
+        self.inspectors.lock().insert(ctx.tile_id, ctx);
+    }
+
+    pub fn close(&self, ctx: &InspectorContext) {
+        self.inspectors.lock().remove(&ctx.tile_id);
+    }
+
+    pub fn get(&self, tile_id: &TileId) -> Option<InspectorContext> {
+        self.inspectors.lock().get(tile_id).cloned()
+    }
+
+    pub fn all(&self) -> Vec<InspectorContext> {
+        self.inspectors.lock().values().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.inspectors.lock().clear();
+    }
 }
@@ -22,6 +22,9 @@
 //! }
 //! ```
 
+use std::ops::ControlFlow;
+
+use blob::Uploads;
 use clap::Parser;
 use futures_util::StreamExt;
 use protocol::ServerPluginMessage;
@@ -37,19 +40,58 @@ use ws::WebSocketFuture;
 pub use tracing;
 pub use tracing_subscriber;
 
+// Provide schema derive to the implementor
+pub use schemars;
+
 // Module re-exports
-pub use display::Display;
-pub use inspector::Inspector;
+pub use ack::Acknowledgement;
+pub use binding::{BindableProperties, BindingError, BindingMessage};
+pub use blob::{Blob, BlobAssembler, BlobError, BlobId, BlobMessage, BlobOptions};
+pub use deep_link::{DeepLinkError, DeepLinkRouter, DeepLinkUrl};
+pub use display::{DISPLAY_READY_VALUE, Display};
+pub use display_stream::{DisplayStream, DisplayStreamStats};
+pub use events::PluginEvent;
+pub use form::{FieldKind, FormField, FormSchema, SelectOption};
+pub use host::{HostInfo, PROTOCOL_VERSION, SDK_NAME, SDK_VERSION};
+pub use inspector::{Inspector, InspectorAction, InspectorErrorMessage, TypedInspector};
 pub use plugin::Plugin;
 pub use protocol::*;
-pub use session::{PluginSessionHandle, SessionError};
+pub use protocol_error::{ProtocolError, ProtocolErrorPolicy, ProtocolErrorStats};
+pub use router::InspectorRouter;
+pub use rpc::{RpcError, RpcMessage};
+pub use rules::{StyleCondition, StyleRule, StyleRules, TileStyle};
+pub use schema::{PropertiesSchemas, SchemaError, SchemaErrors};
+pub use session::{PluginSessionHandle, RPC_CALL_TIMEOUT, SessionError};
+pub use style::{
+    Color, Font, FontSize, StyleError, TileIconBuilder, TileIconOptionsBuilder, TileLabelBuilder,
+};
+pub use text::{FittedLabel, LabelLayout, measure_text};
+pub use theme::Theme;
+pub use tiles::OwnershipMode;
 
+mod ack;
+mod binding;
+mod blob;
+mod deep_link;
 mod display;
+mod display_stream;
+mod events;
+mod form;
+mod host;
 mod inspector;
 mod plugin;
 mod protocol;
+mod protocol_error;
+mod router;
+mod rpc;
+mod rules;
+mod schema;
 mod session;
+mod style;
 mod subscription;
+mod text;
+mod theme;
+mod tiles;
 mod ws;
 
 #[derive(Parser, Debug)]
@@ -71,9 +113,20 @@ where
     // Accept the command line arguments
     let args = Args::parse();
 
+    run_plugin(plugin, args.plugin_id, &args.connect_url).await;
+}
+
+/// Runs the `plugin` connecting to the plugin server at `connect_url` as `plugin_id`
+///
+/// [start_plugin] takes these from the command line arguments Tilepad starts
+/// the plugin with, this can be used directly to run a plugin against a
+/// different server such as a local stand-in server for testing
+pub async fn run_plugin<P>(plugin: P, plugin_id: impl Into<PluginId>, connect_url: &str)
+where
+    P: Plugin,
+{
     // Connect to the server socket
-    let client_request = args
-        .connect_url
+    let client_request = connect_url
         .into_client_request()
         .expect("failed to create client request");
     let (socket, _response) = connect_async(client_request)
@@ -91,7 +144,7 @@ where
 
     // Send registration message
     handle
-        .register(args.plugin_id)
+        .register(plugin_id.into())
         .expect("failed to register plugin");
 
     let msg_rx = PluginSessionRx::new(ws_rx);
@@ -118,9 +171,24 @@ async fn run_handler<P>(
 ) where
     P: Plugin,
 {
+    let mut inspector_router = InspectorRouter::default();
+    plugin.inspector_routes(&mut inspector_router);
+
+    let mut deep_link_router = DeepLinkRouter::default();
+    plugin.deep_link_routes(&mut deep_link_router);
+
+    let accepts_display_uploads = plugin.accepts_display_uploads();
+    let display_uploads = Uploads::default();
+
     while let Some(msg) = msg_rx.next().await {
         let msg = match msg {
             Ok(value) => value,
+            Err(SessionError::Protocol(error)) => {
+                if handle_protocol_error(&mut plugin, &handle, error).is_break() {
+                    break;
+                }
+                continue;
+            }
             Err(cause) => {
                 tracing::error!(?cause, "error processing server message");
                 return;
@@ -129,6 +197,9 @@ async fn run_handler<P>(
 
         // Handle subscriptions
         subscriptions.apply(&msg);
+        handle.observe(&msg);
+        handle.publish_event(&msg);
+        validate_properties(&mut plugin, &handle, &msg);
 
         match msg {
             ServerPluginMessage::Registered { .. } => {
@@ -145,50 +216,67 @@ async fn run_handler<P>(
                 plugin.on_tile_clicked(&handle, ctx, properties);
             }
             ServerPluginMessage::RecvFromInspector { ctx, message } => {
-                plugin.on_inspector_message(
-                    &handle,
-                    Inspector {
-                        ctx,
-                        session: handle.clone(),
-                    },
-                    message,
-                );
+                let inspector = Inspector {
+                    ctx,
+                    session: handle.clone(),
+                };
+
+                if let Some(message) =
+                    inspector_router.handle(&mut plugin, &handle, &inspector, message)
+                {
+                    plugin.on_inspector_message(&handle, inspector, message);
+                }
             }
             ServerPluginMessage::RecvFromDisplay { ctx, message } => {
-                plugin.on_display_message(
-                    &handle,
-                    Display {
-                        ctx,
-                        session: handle.clone(),
-                    },
-                    message,
-                );
+                let display = Display {
+                    ctx,
+                    session: handle.clone(),
+                };
+
+                if accepts_display_uploads && message.get(BlobMessage::KEY).is_some() {
+                    let key = (display.ctx.device_id, display.ctx.tile_id);
+                    let blob =
+                        display_uploads.receive(key, message, |message| display.send(message));
+
+                    if let Some(blob) = blob {
+                        plugin.on_display_upload(&handle, display, blob);
+                    }
+                } else {
+                    plugin.on_display_message(&handle, display, message);
+                }
             }
             ServerPluginMessage::InspectorOpen { ctx } => {
-                plugin.on_inspector_open(
-                    &handle,
-                    Inspector {
-                        ctx,
-                        session: handle.clone(),
-                    },
-                );
+                handle.inspector_opened(ctx.clone());
+
+                let inspector = Inspector {
+                    ctx,
+                    session: handle.clone(),
+                };
+
+                inspector_router.handle_open(&inspector);
+                plugin.on_inspector_open(&handle, inspector);
             }
             ServerPluginMessage::InspectorClose { ctx } => {
-                plugin.on_inspector_close(
-                    &handle,
-                    Inspector {
-                        ctx,
-                        session: handle.clone(),
-                    },
-                );
+                handle.inspector_closed(&ctx);
+
+                let inspector = Inspector {
+                    ctx,
+                    session: handle.clone(),
+                };
+
+                inspector_router.handle_close(&inspector);
+                plugin.on_inspector_close(&handle, inspector);
             }
             ServerPluginMessage::DeepLink { ctx } => {
-                plugin.on_deep_link(&handle, ctx);
+                if let Some(ctx) = deep_link_router.handle(&mut plugin, &handle, ctx) {
+                    plugin.on_deep_link(&handle, ctx);
+                }
             }
             ServerPluginMessage::TileProperties {
                 tile_id,
                 properties,
             } => {
+                inspector_router.handle_tile_properties(&mut plugin, &handle, tile_id, &properties);
                 plugin.on_tile_properties(&handle, tile_id, properties);
             }
 
@@ -199,10 +287,89 @@ async fn run_handler<P>(
             ServerPluginMessage::VisibleTiles { tiles } => {
                 plugin.on_visible_tiles(&handle, tiles);
             }
+
+            // Acknowledgements are resolved when the message is observed
+            ServerPluginMessage::Ack { .. } => {}
+
+            ServerPluginMessage::Error {
+                request_id,
+                code,
+                message,
+            } => {
+                tracing::warn!(?request_id, code, message, "host rejected request");
+            }
+
+            ServerPluginMessage::Unknown {
+                message_type,
+                message,
+            } => {
+                plugin.on_unknown_message(&handle, message_type, message);
+            }
         }
     }
 
     subscriptions.clear();
+    handle.clear_inspectors();
+    handle.clear_observed();
+}
+
+/// Handles a malformed message from the server according to the
+/// [ProtocolErrorPolicy] of the plugin
+fn handle_protocol_error<P>(
+    plugin: &mut P,
+    handle: &PluginSessionHandle,
+    error: ProtocolError,
+) -> ControlFlow<()>
+where
+    P: Plugin,
+{
+    handle.record_protocol_error(&error);
+
+    match plugin.protocol_error_policy() {
+        ProtocolErrorPolicy::Ignore => {
+            tracing::debug!(?error, "ignoring malformed message from server");
+            ControlFlow::Continue(())
+        }
+        ProtocolErrorPolicy::Report => {
+            tracing::warn!(?error, "malformed message from server");
+            plugin.on_protocol_error(handle, error);
+            ControlFlow::Continue(())
+        }
+        ProtocolErrorPolicy::Disconnect => {
+            tracing::error!(?error, "malformed message from server, disconnecting");
+            plugin.on_protocol_error(handle, error);
+            handle.disconnect();
+            ControlFlow::Break(())
+        }
+    }
+}
+
+/// Validates the properties within a message from the server against
+/// the properties schemas, reporting any errors to the plugin
+fn validate_properties<P>(plugin: &mut P, handle: &PluginSessionHandle, msg: &ServerPluginMessage)
+where
+    P: Plugin,
+{
+    let result = match msg {
+        ServerPluginMessage::Properties { properties } => handle
+            .validate_plugin_properties(properties, false)
+            .map_err(|errors| (None, errors)),
+        ServerPluginMessage::TileClicked { ctx, properties } => handle
+            .validate_tile_properties(ctx.tile_id, properties, false)
+            .map_err(|errors| (Some(ctx.tile_id), errors)),
+        ServerPluginMessage::TileProperties {
+            tile_id,
+            properties,
+        } => handle
+            .validate_tile_properties(*tile_id, properties, false)
+            .map_err(|errors| (Some(*tile_id), errors)),
+        _ => Ok(()),
+    };
+
+    if let Err((tile_id, errors)) = result {
+        tracing::warn!(?tile_id, %errors, "received properties do not match schema");
+        plugin.on_invalid_properties(handle, tile_id, errors);
+    }
 }
 
 pub fn setup_tracing() {
@@ -221,3 +388,72 @@ pub fn setup_tracing() {
     // use that subscriber to process traces emitted after this point
     tracing::subscriber::set_global_default(subscriber).expect("failed to setup tracing");
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::ws::{WsMessage, WsRx};
+
+    struct TestPlugin {
+        policy: ProtocolErrorPolicy,
+        reported: usize,
+    }
+
+    impl Plugin for TestPlugin {
+        fn protocol_error_policy(&self) -> ProtocolErrorPolicy {
+            self.policy
+        }
+
+        fn on_protocol_error(&mut self, _session: &PluginSessionHandle, _error: ProtocolError) {
+            self.reported += 1;
+        }
+    }
+
+    fn session() -> (PluginSessionHandle, WsRx) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (PluginSessionHandle::new(tx, Subscriptions::default()), rx)
+    }
+
+    /// Handles a malformed message with the plugin using `policy`, returns
+    /// the outcome and whether the plugin was disconnected
+    fn handle(policy: ProtocolErrorPolicy) -> (TestPlugin, ControlFlow<()>, bool) {
+        let (session, mut rx) = session();
+        let mut plugin = TestPlugin {
+            policy,
+            reported: 0,
+        };
+
+        let error = ProtocolError::BinaryMessage { raw: vec![0] };
+        let flow = handle_protocol_error(&mut plugin, &session, error);
+        let disconnected = matches!(rx.try_recv(), Ok(WsMessage::Close(_)));
+
+        assert_eq!(session.protocol_error_stats().binary, 1);
+        (plugin, flow, disconnected)
+    }
+
+    #[test]
+    fn ignored_protocol_errors_are_counted() {
+        let (plugin, flow, disconnected) = handle(ProtocolErrorPolicy::Ignore);
+        assert_eq!(plugin.reported, 0);
+        assert!(flow.is_continue());
+        assert!(!disconnected);
+    }
+
+    #[test]
+    fn reported_protocol_errors_reach_plugin() {
+        let (plugin, flow, disconnected) = handle(ProtocolErrorPolicy::Report);
+        assert_eq!(plugin.reported, 1);
+        assert!(flow.is_continue());
+        assert!(!disconnected);
+    }
+
+    #[test]
+    fn disconnect_policy_closes_connection() {
+        let (plugin, flow, disconnected) = handle(ProtocolErrorPolicy::Disconnect);
+        assert_eq!(plugin.reported, 1);
+        assert!(flow.is_break());
+        assert!(disconnected);
+    }
+}
@@ -1,7 +1,12 @@
 use crate::{
+    blob::Blob,
+    deep_link::DeepLinkRouter,
     display::Display,
     inspector::Inspector,
     protocol::{DeepLinkContext, DeviceId, TileId, TileInteractionContext, TileModel},
+    protocol_error::{ProtocolError, ProtocolErrorPolicy},
+    router::InspectorRouter,
+    schema::SchemaErrors,
     session::PluginSessionHandle,
 };
 
@@ -24,6 +29,22 @@ pub trait Plugin {
     /// * `properties` - The current plugin properties
     fn on_properties(&mut self, session: &PluginSessionHandle, properties: serde_json::Value) {}
 
+    /// Invoked when properties received from Tilepad do not match the schemas
+    /// set with [PluginSessionHandle::set_properties_schemas], the properties
+    /// are still passed on to the other callbacks
+    ///
+    /// # Arguments
+    /// * `session` - The current session
+    /// * `tile_id` - ID of the tile the properties are for, [None] for the plugin properties
+    /// * `errors`  - Field errors from validating the properties
+    fn on_invalid_properties(
+        &mut self,
+        session: &PluginSessionHandle,
+        tile_id: Option<TileId>,
+        errors: SchemaErrors,
+    ) {
+    }
+
     /// Invoked when a tiles properties are received from Tilepad,
     /// this will occur when the plugin calls [PluginSessionHandle::request_tile_properties] or  [PluginSessionHandle::get_tile_properties]
     ///
@@ -39,9 +60,23 @@ pub trait Plugin {
     ) {
     }
 
+    /// Invoked once before the plugin starts handling messages to register
+    /// the routes for messages received from inspectors
+    ///
+    /// # Arguments
+    /// * `router` - Router to register the routes on
+    fn inspector_routes(&self, router: &mut InspectorRouter<Self>)
+    where
+        Self: Sized,
+    {
+    }
+
     /// Invoked when the plugin receives a message from the inspector,
     /// this message structure is defined by the developer   
     ///
+    /// Messages handled by a route from [Plugin::inspector_routes] are
+    /// not passed to this method
+    ///
     /// # Arguments
     /// * `session`  - The current session
     /// * `inspector - Inspector to send messages back
@@ -56,6 +91,9 @@ pub trait Plugin {
     /// Invoked when the plugin receives a message from a display,
     /// this message structure is defined by the developer   
     ///
+    /// The ready message, see [DISPLAY_READY_VALUE](crate::DISPLAY_READY_VALUE), is also passed
+    /// to this method after any stored display state has been resent
+    ///
     /// # Arguments
     /// * `session` - The current session
     /// * `display` - Display to send messages back
@@ -68,6 +106,25 @@ pub trait Plugin {
     ) {
     }
 
+    /// Whether blobs uploaded by displays are reassembled and passed to
+    /// [Plugin::on_display_upload], when disabled the blob messages are
+    /// passed to [Plugin::on_display_message] unchanged
+    ///
+    /// Uploads larger than [BlobAssembler::DEFAULT_MAX_SIZE](crate::BlobAssembler::DEFAULT_MAX_SIZE)
+    /// are rejected
+    fn accepts_display_uploads(&self) -> bool {
+        false
+    }
+
+    /// Invoked when a display has finished uploading a blob, requires
+    /// [Plugin::accepts_display_uploads]
+    ///
+    /// # Arguments
+    /// * `session` - The current session
+    /// * `display` - Display that uploaded the blob
+    /// * `blob`    - The uploaded blob
+    fn on_display_upload(&mut self, session: &PluginSessionHandle, display: Display, blob: Blob) {}
+
     /// Invoked when the inspector is opened for a tile
     ///
     /// # Arguments
@@ -82,8 +139,22 @@ pub trait Plugin {
     /// * `inspector - Inspector to send messages back
     fn on_inspector_close(&mut self, session: &PluginSessionHandle, inspector: Inspector) {}
 
+    /// Invoked once before the plugin starts handling messages to register
+    /// the routes for deep links received by the plugin
+    ///
+    /// # Arguments
+    /// * `router` - Router to register the routes on
+    fn deep_link_routes(&self, router: &mut DeepLinkRouter<Self>)
+    where
+        Self: Sized,
+    {
+    }
+
     /// Invoked when a deep link is received for the plugin
     ///
+    /// Deep links handled by a route from [Plugin::deep_link_routes] are
+    /// not passed to this method
+    ///
     /// # Arguments
     /// * `session` - The current session
     /// * `ctx`     - Information about the deep-link
@@ -123,4 +194,36 @@ pub trait Plugin {
     /// * `session`   - The current session
     /// * `tiles`     - The current visible tiles
     fn on_visible_tiles(&mut self, session: &PluginSessionHandle, tiles: Vec<TileModel>) {}
+
+    /// Policy for handling malformed messages received from the server,
+    /// called each time a malformed message is received
+    fn protocol_error_policy(&self) -> ProtocolErrorPolicy {
+        ProtocolErrorPolicy::default()
+    }
+
+    /// Invoked when a malformed message is received from the server and
+    /// the [Plugin::protocol_error_policy] is [ProtocolErrorPolicy::Report]
+    /// or [ProtocolErrorPolicy::Disconnect]
+    ///
+    /// # Arguments
+    /// * `session` - The current session
+    /// * `error`   - The error including the raw message
+    fn on_protocol_error(&mut self, session: &PluginSessionHandle, error: ProtocolError) {}
+
+    /// Invoked when a message with a type not known to this version of the
+    /// SDK is received, allows handling messages added to Tilepad before
+    /// the SDK is updated. Use [PluginSessionHandle::send_raw] to send
+    /// messages the SDK does not support
+    ///
+    /// # Arguments
+    /// * `session`      - The current session
+    /// * `message_type` - The `type` field of the message
+    /// * `message`      - The complete raw message
+    fn on_unknown_message(
+        &mut self,
+        session: &PluginSessionHandle,
+        message_type: String,
+        message: serde_json::Value,
+    ) {
+    }
 }
@@ -1,6 +1,11 @@
-use serde::{Deserialize, Serialize};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, IntoDeserializer},
+};
 use uuid::Uuid;
 
+use crate::host::{HostInfo, SdkInfo};
+
 pub type PluginId = String;
 pub type IconPackId = String;
 pub type ActionId = String;
@@ -78,6 +83,41 @@ pub struct TileConfig {
     pub icon: TileIcon,
     /// Label to display on top of the tile
     pub label: TileLabel,
+    /// Options for how the icon is displayed
+    #[serde(default)]
+    pub icon_options: Option<TileIconOptions>,
+}
+
+/// Update to the configuration of a tile, only the
+/// specified parts of the configuration are changed
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TileConfigUpdate {
+    /// New icon for the tile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<TileIcon>,
+    /// New icon options for the tile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_options: Option<TileIconOptions>,
+    /// New label for the tile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<TileLabel>,
+}
+
+impl TileConfigUpdate {
+    pub fn icon(mut self, icon: TileIcon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    pub fn icon_options(mut self, icon_options: TileIconOptions) -> Self {
+        self.icon_options = Some(icon_options);
+        self
+    }
+
+    pub fn label(mut self, label: TileLabel) -> Self {
+        self.label = Some(label);
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,7 +140,7 @@ pub struct TilePosition {
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(tag = "type")]
+#[serde(tag = "type", remote = "Self")]
 pub enum TileIcon {
     /// No icon
     #[default]
@@ -139,8 +179,52 @@ pub enum TileIcon {
         path: String,
     },
 
-    #[serde(other)]
-    Unknown,
+    /// Icon type not known to this version of the SDK, the raw
+    /// icon is kept so it is serialized back unchanged
+    #[serde(skip)]
+    Unknown(serde_json::Value),
+}
+
+impl TileIcon {
+    /// Icon types known to this version of the SDK, must match
+    /// the variants serde deserializes
+    pub const KNOWN_TYPES: &[&str] = &[
+        "None",
+        "PluginIcon",
+        "IconPack",
+        "Url",
+        "Uploaded",
+        "Display",
+    ];
+}
+
+impl Serialize for TileIcon {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            TileIcon::Unknown(value) => value.serialize(serializer),
+            icon => TileIcon::serialize(icon, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TileIcon {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+
+        // Icons with a known type must be valid, only unknown types are kept raw
+        match value.get("type").and_then(serde_json::Value::as_str) {
+            Some(icon_type) if TileIcon::KNOWN_TYPES.contains(&icon_type) => {
+                TileIcon::deserialize(value).map_err(de::Error::custom)
+            }
+            _ => Ok(TileIcon::Unknown(value)),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -163,14 +247,50 @@ pub struct TileLabel {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(remote = "Self")]
 pub enum LabelAlign {
     #[default]
     Bottom,
     Middle,
     Top,
 
-    #[serde(other)]
-    Unknown,
+    /// Alignment not known to this version of the SDK, the raw
+    /// value is kept so it is serialized back unchanged
+    #[serde(skip)]
+    Unknown(String),
+}
+
+impl LabelAlign {
+    /// Alignments known to this version of the SDK, must match
+    /// the variants serde deserializes
+    pub const KNOWN_VALUES: &[&str] = &["Bottom", "Middle", "Top"];
+}
+
+impl Serialize for LabelAlign {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            LabelAlign::Unknown(value) => serializer.serialize_str(value),
+            align => LabelAlign::serialize(align, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LabelAlign {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+
+        if !LabelAlign::KNOWN_VALUES.contains(&value.as_str()) {
+            return Ok(LabelAlign::Unknown(value));
+        }
+
+        LabelAlign::deserialize(value.into_deserializer())
+    }
 }
 
 /// Plugin message coming from the client side
@@ -178,7 +298,13 @@ pub enum LabelAlign {
 #[serde(tag = "type")]
 pub(crate) enum ClientPluginMessage {
     /// Register the current plugin with the server
-    RegisterPlugin { plugin_id: PluginId },
+    RegisterPlugin {
+        plugin_id: PluginId,
+        /// Details about the SDK the plugin is using
+        sdk: SdkInfo,
+        /// Version of the plugin protocol supported by the SDK
+        protocol_version: u32,
+    },
 
     /// Request the current plugin properties
     GetProperties,
@@ -232,6 +358,13 @@ pub(crate) enum ClientPluginMessage {
     /// Set the current label for a tile
     SetTileLabel { tile_id: TileId, label: TileLabel },
 
+    /// Set the icon, icon options and label for a tile at once
+    SetTileConfig {
+        tile_id: TileId,
+        #[serde(flatten)]
+        config: TileConfigUpdate,
+    },
+
     /// Get all currently visible tiles
     GetVisibleTiles,
 
@@ -255,8 +388,10 @@ pub(crate) enum ClientPluginMessage {
 pub(crate) enum ServerPluginMessage {
     /// Plugin has registered with the server
     Registered {
-        #[allow(unused)]
         plugin_id: PluginId,
+        /// Details about the host
+        #[serde(flatten)]
+        host: HostInfo,
     },
 
     /// Properties received from the server
@@ -307,9 +442,53 @@ pub(crate) enum ServerPluginMessage {
         /// Tiles that are currently visible
         tiles: Vec<TileModel>,
     },
+
+    /// Host acknowledged a request sent with a request ID
+    Ack { request_id: u64 },
+
+    /// Host rejected a request
+    Error {
+        /// ID of the request that was rejected, [None] when the
+        /// error is not tied to a request
+        #[serde(default)]
+        request_id: Option<u64>,
+        /// Code identifying the error
+        code: String,
+        /// Description of the error
+        message: String,
+    },
+
+    /// Message with a type not known to this version of the SDK,
+    /// created from the raw message rather than deserialized
+    #[serde(skip)]
+    Unknown {
+        message_type: String,
+        message: serde_json::Value,
+    },
+}
+
+impl ServerPluginMessage {
+    /// Message types known to this version of the SDK, must match
+    /// the variants serde deserializes
+    pub const KNOWN_TYPES: &[&str] = &[
+        "Registered",
+        "Properties",
+        "TileClicked",
+        "RecvFromInspector",
+        "RecvFromDisplay",
+        "InspectorOpen",
+        "InspectorClose",
+        "DeepLink",
+        "TileProperties",
+        "DeviceTiles",
+        "VisibleTiles",
+        "Ack",
+        "Error",
+    ];
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub enum DeviceIndicator {
     Error,
     Success,
@@ -318,6 +497,138 @@ pub enum DeviceIndicator {
     /// Clear the active indicator
     None,
 
-    #[serde(other)]
-    Unknown,
+    /// Indicator not known to this version of the SDK, the raw
+    /// value is kept so it is serialized back unchanged
+    #[serde(skip)]
+    Unknown(String),
+}
+
+impl DeviceIndicator {
+    /// Indicators known to this version of the SDK, must match
+    /// the variants serde deserializes
+    pub const KNOWN_VALUES: &[&str] = &["Error", "Success", "Warning", "Loading", "None"];
+}
+
+impl Serialize for DeviceIndicator {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            DeviceIndicator::Unknown(value) => serializer.serialize_str(value),
+            indicator => DeviceIndicator::serialize(indicator, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DeviceIndicator {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+
+        if !DeviceIndicator::KNOWN_VALUES.contains(&value.as_str()) {
+            return Ok(DeviceIndicator::Unknown(value));
+        }
+
+        DeviceIndicator::deserialize(value.into_deserializer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+
+    use super::*;
+
+    /// Checks that `value` deserializes and serializes back unchanged
+    fn round_trip<T>(value: serde_json::Value)
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let parsed: T = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(parsed).unwrap(), value);
+    }
+
+    /// Variants the serde derived deserializer expects, taken from the
+    /// error for a value with an unknown variant name
+    fn expected_variants(error: serde_json::Error) -> Vec<String> {
+        let error = error.to_string();
+        let (_, expected) = error
+            .split_once("expected one of ")
+            .expect("error should list the expected variants");
+        expected
+            .split(", ")
+            .map(|variant| variant.trim_matches('`').to_string())
+            .collect()
+    }
+
+    #[test]
+    fn known_types_match_deserialized_messages() {
+        let error =
+            serde_json::from_value::<ServerPluginMessage>(json!({ "type": "\0" })).unwrap_err();
+        assert_eq!(expected_variants(error), ServerPluginMessage::KNOWN_TYPES);
+        assert!(!ServerPluginMessage::KNOWN_TYPES.contains(&"Unknown"));
+    }
+
+    #[test]
+    fn known_values_match_deserialized_enums() {
+        let error = TileIcon::deserialize(json!({ "type": "\0" })).unwrap_err();
+        assert_eq!(expected_variants(error), TileIcon::KNOWN_TYPES);
+
+        let error = LabelAlign::deserialize(json!("\0")).unwrap_err();
+        assert_eq!(expected_variants(error), LabelAlign::KNOWN_VALUES);
+
+        let error = DeviceIndicator::deserialize(json!("\0")).unwrap_err();
+        assert_eq!(expected_variants(error), DeviceIndicator::KNOWN_VALUES);
+    }
+
+    #[test]
+    fn tile_icon_round_trips() {
+        round_trip::<TileIcon>(json!({ "type": "None" }));
+        round_trip::<TileIcon>(json!({ "type": "PluginIcon", "plugin_id": "a", "icon": "b" }));
+        round_trip::<TileIcon>(json!({ "type": "IconPack", "pack_id": "a", "path": "b" }));
+        round_trip::<TileIcon>(json!({ "type": "Url", "src": "https://example.com" }));
+        round_trip::<TileIcon>(json!({ "type": "Uploaded", "path": "a" }));
+        round_trip::<TileIcon>(json!({ "type": "Display", "path": "a" }));
+        round_trip::<TileIcon>(json!({ "type": "Gradient", "from": "#fff", "to": "#000" }));
+    }
+
+    #[test]
+    fn tile_icon_keeps_unknown_types() {
+        let icon: TileIcon = serde_json::from_value(json!({ "type": "Gradient" })).unwrap();
+        assert_eq!(icon, TileIcon::Unknown(json!({ "type": "Gradient" })));
+    }
+
+    #[test]
+    fn tile_icon_rejects_malformed_known_types() {
+        assert!(serde_json::from_value::<TileIcon>(json!({ "type": "Url" })).is_err());
+        assert!(
+            serde_json::from_value::<TileIcon>(json!({ "type": "Display", "path": 1 })).is_err()
+        );
+    }
+
+    #[test]
+    fn label_align_round_trips() {
+        for value in ["Bottom", "Middle", "Top", "Justify"] {
+            round_trip::<LabelAlign>(json!(value));
+        }
+
+        let align: LabelAlign = serde_json::from_value(json!("Justify")).unwrap();
+        assert_eq!(align, LabelAlign::Unknown("Justify".to_string()));
+        assert!(serde_json::from_value::<LabelAlign>(json!({ "Top": null })).is_err());
+    }
+
+    #[test]
+    fn device_indicator_round_trips() {
+        for value in ["Error", "Success", "Warning", "Loading", "None", "Pulse"] {
+            round_trip::<DeviceIndicator>(json!(value));
+        }
+
+        let indicator: DeviceIndicator = serde_json::from_value(json!("Pulse")).unwrap();
+        assert!(matches!(indicator, DeviceIndicator::Unknown(value) if value == "Pulse"));
+        assert!(serde_json::from_value::<DeviceIndicator>(json!(1)).is_err());
+    }
 }
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use thiserror::Error;
+
+/// Malformed message received from the server
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    /// Text message that could not be deserialized
+    #[error("invalid message: {error}")]
+    InvalidMessage {
+        /// Raw text of the message
+        raw: String,
+        /// Error from deserializing the message
+        error: serde_json::Error,
+    },
+
+    /// Binary message, the protocol only uses text messages
+    #[error("unexpected binary message")]
+    BinaryMessage {
+        /// Raw bytes of the message
+        raw: Vec<u8>,
+    },
+}
+
+/// How the plugin responds to a [ProtocolError], set using
+/// [Plugin::protocol_error_policy](crate::Plugin::protocol_error_policy)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolErrorPolicy {
+    /// Skip the message without reporting it to the plugin
+    Ignore,
+    /// Skip the message and report it to [Plugin::on_protocol_error](crate::Plugin::on_protocol_error)
+    #[default]
+    Report,
+    /// Report the message to [Plugin::on_protocol_error](crate::Plugin::on_protocol_error)
+    /// and disconnect from the server
+    Disconnect,
+}
+
+/// Counts of the messages rejected because of a [ProtocolError]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolErrorStats {
+    /// Number of text messages that could not be deserialized
+    pub invalid: u64,
+    /// Number of binary messages
+    pub binary: u64,
+}
+
+impl ProtocolErrorStats {
+    /// Total number of rejected messages
+    pub fn total(&self) -> u64 {
+        self.invalid + self.binary
+    }
+}
+
+/// Shared counters for rejected messages
+#[derive(Default, Clone)]
+pub(crate) struct ProtocolErrorCounters {
+    stats: Arc<Mutex<ProtocolErrorStats>>,
+}
+
+impl ProtocolErrorCounters {
+    pub fn record(&self, error: &ProtocolError) {
+        let stats = &mut *self.stats.lock();
+        match error {
+            ProtocolError::InvalidMessage { .. } => stats.invalid += 1,
+            ProtocolError::BinaryMessage { .. } => stats.binary += 1,
+        }
+    }
+
+    pub fn get(&self) -> ProtocolErrorStats {
+        *self.stats.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_message() -> ProtocolError {
+        let raw = "{".to_string();
+        let error = serde_json::from_str::<serde_json::Value>(&raw).unwrap_err();
+        ProtocolError::InvalidMessage { raw, error }
+    }
+
+    #[test]
+    fn counts_rejected_messages() {
+        let counters = ProtocolErrorCounters::default();
+        assert_eq!(counters.get(), ProtocolErrorStats::default());
+
+        counters.record(&invalid_message());
+        counters.record(&invalid_message());
+        counters
+            .clone()
+            .record(&ProtocolError::BinaryMessage { raw: vec![1, 2] });
+
+        let stats = counters.get();
+        assert_eq!(
+            stats,
+            ProtocolErrorStats {
+                invalid: 2,
+                binary: 1
+            }
+        );
+        assert_eq!(stats.total(), 3);
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    binding::{BindableProperties, BindingMessage, PropertyBindings},
+    blob::{Blob, BlobMessage, BlobUploads},
+    form::{FormRoutes, FormSchema, SelectOption},
+    inspector::{Inspector, InspectorAction, InspectorErrorMessage, TypedInspector},
+    protocol::TileId,
+    rpc::{RpcError, RpcMessage},
+    session::PluginSessionHandle,
+};
+
+/// Handler for an RPC method with the params and result erased to JSON
+type MethodHandler<P> = Box<
+    dyn Fn(
+        &mut P,
+        &PluginSessionHandle,
+        &Inspector,
+        serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError>,
+>;
+
+/// Handler for the messages of an [InspectorAction] with the message erased to JSON
+type ActionHandler<P> = Box<
+    dyn Fn(&mut P, &PluginSessionHandle, &Inspector, serde_json::Value) -> Result<(), RpcError>,
+>;
+
+/// Router for messages received from inspectors
+///
+/// Routes are registered in [Plugin::inspector_routes](crate::Plugin::inspector_routes),
+/// messages that are not handled by a route are passed on to
+/// [Plugin::on_inspector_message](crate::Plugin::on_inspector_message)
+pub struct InspectorRouter<P> {
+    /// RPC methods the inspector can invoke
+    methods: HashMap<String, MethodHandler<P>>,
+    /// Typed message handlers for specific actions
+    actions: HashMap<String, ActionHandler<P>>,
+    /// Bindings between inspectors and tile properties
+    bindings: PropertyBindings<P>,
+    /// Form schemas served to inspectors
+    forms: FormRoutes<P>,
+    /// Uploads received from inspectors
+    uploads: BlobUploads<P>,
+}
+
+impl<P> Default for InspectorRouter<P> {
+    fn default() -> Self {
+        Self {
+            methods: Default::default(),
+            actions: Default::default(),
+            bindings: Default::default(),
+            forms: Default::default(),
+            uploads: Default::default(),
+        }
+    }
+}
+
+impl<P> InspectorRouter<P> {
+    /// Registers an RPC method named `name` that the inspector can invoke
+    ///
+    /// The request params are deserialized into `Params`, requests with
+    /// params that fail to deserialize are responded to with an
+    /// [RpcError::INVALID_PARAMS] error. The result of the handler is
+    /// sent back to the inspector as the response, see [RpcMessage]
+    /// for the message format
+    ///
+    /// Requests are only handled by the router once a method or form is
+    /// registered, until then they are passed on to
+    /// [Plugin::on_inspector_message](crate::Plugin::on_inspector_message)
+    pub fn method<Params, R, F>(&mut self, name: impl Into<String>, handler: F) -> &mut Self
+    where
+        Params: DeserializeOwned,
+        R: Serialize,
+        F: Fn(&mut P, &PluginSessionHandle, &Inspector, Params) -> Result<R, RpcError> + 'static,
+    {
+        self.methods.insert(
+            name.into(),
+            Box::new(move |plugin, session, inspector, params| {
+                let params: Params =
+                    serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+                let result = handler(plugin, session, inspector, params)?;
+                serde_json::to_value(result).map_err(|cause| RpcError::internal(cause.to_string()))
+            }),
+        );
+        self
+    }
+
+    /// Registers a handler for messages from the inspectors of action `A`
+    ///
+    /// Messages are deserialized into [InspectorAction::InspectorIn] before
+    /// the handler is invoked, messages that fail to deserialize are reported
+    /// back to the inspector as an [InspectorErrorMessage]
+    pub fn action<A, F>(&mut self, handler: F) -> &mut Self
+    where
+        A: InspectorAction,
+        F: Fn(&mut P, &PluginSessionHandle, TypedInspector<A>, A::InspectorIn) + 'static,
+    {
+        self.actions.insert(
+            A::ACTION_ID.to_string(),
+            Box::new(move |plugin, session, inspector, message| {
+                let message: A::InspectorIn = serde_json::from_value(message)
+                    .map_err(|cause| RpcError::new(RpcError::INVALID_MESSAGE, cause.to_string()))?;
+                if let Some(inspector) = TypedInspector::new(inspector.clone()) {
+                    handler(plugin, session, inspector, message);
+                }
+                Ok(())
+            }),
+        );
+        self
+    }
+
+    /// Binds the inspectors for `action_id` to the properties of their tile
+    ///
+    /// When a bound inspector is opened the current tile properties are
+    /// pushed to it, edits received from the inspector are validated as `T`
+    /// and applied as partial updates to the tile properties before
+    /// `on_change` is invoked with the updated properties. Edits received
+    /// before the tile properties are known are applied once they have
+    /// been received. See [BindingMessage] for the message format
+    pub fn bind_properties<T, F>(&mut self, action_id: impl Into<String>, on_change: F) -> &mut Self
+    where
+        T: BindableProperties,
+        F: Fn(&mut P, &PluginSessionHandle, &Inspector, &T) + 'static,
+    {
+        self.bindings.bind(action_id.into(), on_change);
+        self
+    }
+
+    /// Registers the settings form `schema` for the inspectors of `action_id`
+    ///
+    /// The schema is served to the inspector through the
+    /// [FormSchema::SCHEMA_METHOD] RPC method
+    pub fn form(&mut self, action_id: impl Into<String>, schema: FormSchema) -> &mut Self {
+        self.forms.add_schema(action_id.into(), schema);
+        self
+    }
+
+    /// Registers a `handler` providing the options for dynamic select fields
+    /// using `source`
+    ///
+    /// The options are served to the inspector through the
+    /// [FormSchema::OPTIONS_METHOD] RPC method
+    pub fn form_options<F>(&mut self, source: impl Into<String>, handler: F) -> &mut Self
+    where
+        F: Fn(&mut P, &PluginSessionHandle, &Inspector) -> Result<Vec<SelectOption>, RpcError>
+            + 'static,
+    {
+        self.forms.add_options(source.into(), handler);
+        self
+    }
+
+    /// Registers a `handler` for blobs uploaded by inspectors
+    ///
+    /// Uploads are sent as chunked transfers using the [BlobMessage] envelope
+    /// and reassembled before the handler is invoked, transfers that fail are
+    /// aborted by sending an [BlobMessage::Abort] back to the inspector.
+    /// Uploads larger than [BlobAssembler::DEFAULT_MAX_SIZE](crate::BlobAssembler::DEFAULT_MAX_SIZE)
+    /// are rejected
+    pub fn upload<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(&mut P, &PluginSessionHandle, &Inspector, Blob) + 'static,
+    {
+        self.uploads.set_handler(handler);
+        self
+    }
+
+    /// Handles an inspector being opened
+    pub(crate) fn handle_open(&self, inspector: &Inspector) {
+        self.bindings.handle_open(inspector);
+    }
+
+    /// Handles an inspector being closed
+    pub(crate) fn handle_close(&self, inspector: &Inspector) {
+        self.bindings.handle_close(inspector);
+        self.uploads.handle_close(inspector);
+    }
+
+    /// Handles properties being received for a tile
+    pub(crate) fn handle_tile_properties(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        tile_id: TileId,
+        properties: &serde_json::Value,
+    ) {
+        self.bindings
+            .handle_tile_properties(plugin, session, tile_id, properties);
+    }
+
+    /// Handles a message from the `inspector`, returns the message
+    /// back when it was not handled by the router
+    pub(crate) fn handle(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        inspector: &Inspector,
+        message: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        if message.get(BindingMessage::KEY).is_some() {
+            let message = self.bindings.handle(plugin, session, inspector, message)?;
+            return self.handle_action(plugin, session, inspector, message);
+        }
+
+        if message.get(BlobMessage::KEY).is_some() {
+            let message = self.uploads.handle(plugin, session, inspector, message)?;
+            return self.handle_action(plugin, session, inspector, message);
+        }
+
+        let Some(kind) = message.get(RpcMessage::KEY) else {
+            return self.handle_action(plugin, session, inspector, message);
+        };
+
+        let id = message.get("id").and_then(serde_json::Value::as_u64);
+        let is_response = kind == "Response";
+
+        // Only responses to calls made by the plugin and requests when RPC
+        // methods are registered are handled, other messages using the
+        // envelope are left to the plugin
+        let is_rpc = if is_response {
+            id.is_some_and(|id| session.has_rpc_call(&inspector.ctx, id))
+        } else {
+            self.has_rpc_methods()
+        };
+
+        if !is_rpc {
+            return self.handle_action(plugin, session, inspector, message);
+        }
+
+        let message: RpcMessage = match serde_json::from_value(message) {
+            Ok(value) => value,
+            Err(cause) => {
+                tracing::error!(?cause, "invalid rpc message from inspector");
+
+                // Let the inspector know its request could not be understood
+                if let Some(id) = id
+                    && !is_response
+                {
+                    let response = RpcMessage::response(id, Err(RpcError::invalid_request(cause)));
+                    if let Err(cause) = inspector.send(response) {
+                        tracing::error!(?cause, "failed to send rpc response");
+                    }
+                }
+
+                return None;
+            }
+        };
+
+        match message {
+            RpcMessage::Request { id, method, params } => {
+                let outcome = match self.methods.get(&method) {
+                    Some(handler) => handler(plugin, session, inspector, params),
+                    None => self
+                        .forms
+                        .handle(plugin, session, inspector, &method, params)
+                        .unwrap_or_else(|| Err(RpcError::method_not_found(&method))),
+                };
+
+                if let Err(cause) = inspector.send(RpcMessage::response(id, outcome)) {
+                    tracing::error!(?cause, "failed to send rpc response");
+                }
+            }
+            RpcMessage::Response { id, result, error } => {
+                let outcome = match (result, error) {
+                    (_, Some(error)) => Err(error),
+                    (result, None) => Ok(result.unwrap_or_default()),
+                };
+
+                session.resolve_rpc_call(&inspector.ctx, id, outcome);
+            }
+        }
+
+        None
+    }
+
+    /// Checks whether any RPC methods are registered, including the
+    /// methods serving forms
+    fn has_rpc_methods(&self) -> bool {
+        !self.methods.is_empty() || !self.forms.is_empty()
+    }
+
+    /// Handles a message for an action registered with [InspectorRouter::action]
+    fn handle_action(
+        &self,
+        plugin: &mut P,
+        session: &PluginSessionHandle,
+        inspector: &Inspector,
+        message: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        let Some(handler) = self.actions.get(&inspector.ctx.action_id) else {
+            return Some(message);
+        };
+
+        if let Err(error) = handler(plugin, session, inspector, message) {
+            tracing::warn!(?error, "invalid message from inspector");
+
+            if let Err(cause) = inspector.send(InspectorErrorMessage { error }) {
+                tracing::error!(?cause, "failed to send error to inspector");
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        protocol::InspectorContext,
+        subscription::Subscriptions,
+        ws::{WsMessage, WsRx},
+    };
+
+    fn inspector() -> (Inspector, WsRx) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let session = PluginSessionHandle::new(tx, Subscriptions::default());
+        let ctx = InspectorContext {
+            profile_id: Uuid::new_v4(),
+            folder_id: Uuid::new_v4(),
+            plugin_id: "plugin".to_string(),
+            action_id: "action".to_string(),
+            tile_id: Uuid::new_v4(),
+        };
+        (Inspector { session, ctx }, rx)
+    }
+
+    /// Drains the messages sent to the inspector
+    fn sent(rx: &mut WsRx) -> Vec<serde_json::Value> {
+        let mut messages = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            if let WsMessage::Text(text) = message {
+                let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+                messages.push(message["message"].clone());
+            }
+        }
+        messages
+    }
+
+    fn handle(
+        router: &InspectorRouter<()>,
+        inspector: &Inspector,
+        message: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        router.handle(&mut (), &inspector.session, inspector, message)
+    }
+
+    fn router() -> InspectorRouter<()> {
+        let mut router = InspectorRouter::default();
+        router.method("double", |_, _, _, value: u32| Ok(value * 2));
+        router
+    }
+
+    #[test]
+    fn responds_to_requests() {
+        let router = router();
+        let (inspector, mut rx) = inspector();
+
+        let request = json!({ "rpc": "Request", "id": 3, "method": "double", "params": 21 });
+        assert!(handle(&router, &inspector, request).is_none());
+        assert_eq!(
+            sent(&mut rx),
+            [json!({ "rpc": "Response", "id": 3, "result": 42 })]
+        );
+    }
+
+    #[test]
+    fn responds_with_method_errors() {
+        let router = router();
+        let (inspector, mut rx) = inspector();
+
+        let request = json!({ "rpc": "Request", "id": 1, "method": "triple", "params": 1 });
+        assert!(handle(&router, &inspector, request).is_none());
+        let request = json!({ "rpc": "Request", "id": 2, "method": "double", "params": "a" });
+        assert!(handle(&router, &inspector, request).is_none());
+
+        let sent = sent(&mut rx);
+        assert_eq!(sent[0]["id"], 1);
+        assert_eq!(sent[0]["error"]["code"], RpcError::METHOD_NOT_FOUND);
+        assert_eq!(sent[1]["id"], 2);
+        assert_eq!(sent[1]["error"]["code"], RpcError::INVALID_PARAMS);
+    }
+
+    #[test]
+    fn responds_to_invalid_requests() {
+        let router = router();
+        let (inspector, mut rx) = inspector();
+
+        let request = json!({ "rpc": "Request", "id": 4 });
+        assert!(handle(&router, &inspector, request).is_none());
+
+        let sent = sent(&mut rx);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["rpc"], "Response");
+        assert_eq!(sent[0]["id"], 4);
+        assert_eq!(sent[0]["error"]["code"], RpcError::INVALID_REQUEST);
+    }
+
+    #[test]
+    fn passes_requests_through_without_methods() {
+        let router = InspectorRouter::<()>::default();
+        let (inspector, mut rx) = inspector();
+
+        let request = json!({ "rpc": "Request", "id": 1, "method": "double" });
+        assert_eq!(handle(&router, &inspector, request.clone()), Some(request));
+        assert!(sent(&mut rx).is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolves_responses_to_pending_calls() {
+        let router = InspectorRouter::<()>::default();
+        let (inspector, mut rx) = inspector();
+
+        let call = tokio::spawn({
+            let inspector = inspector.clone();
+            async move {
+                inspector
+                    .session
+                    .call_inspector::<_, u32>(inspector.ctx, "getCount", ())
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+        let id = sent(&mut rx)[0]["id"].clone();
+
+        let unknown = json!({ "rpc": "Response", "id": 100, "result": 1 });
+        assert_eq!(handle(&router, &inspector, unknown.clone()), Some(unknown));
+
+        let response = json!({ "rpc": "Response", "id": id, "result": 7 });
+        assert!(handle(&router, &inspector, response).is_none());
+        assert_eq!(call.await.unwrap().unwrap(), 7);
+    }
+
+    struct Counter;
+
+    #[derive(serde::Deserialize)]
+    enum CounterIn {
+        Increment { by: u32 },
+    }
+
+    impl InspectorAction for Counter {
+        const ACTION_ID: &'static str = "action";
+        type InspectorIn = CounterIn;
+        type InspectorOut = u32;
+    }
+
+    fn counter_router() -> InspectorRouter<u32> {
+        let mut router = InspectorRouter::default();
+        router.action::<Counter, _>(|count, _, inspector, message| match message {
+            CounterIn::Increment { by } => {
+                *count += by;
+                inspector.send(*count).unwrap();
+            }
+        });
+        router
+    }
+
+    #[test]
+    fn dispatches_action_messages() {
+        let router = counter_router();
+        let (inspector, mut rx) = inspector();
+        let mut count = 0;
+
+        let message = json!({ "Increment": { "by": 2 } });
+        assert!(
+            router
+                .handle(&mut count, &inspector.session, &inspector, message)
+                .is_none()
+        );
+        assert_eq!(count, 2);
+        assert_eq!(sent(&mut rx), [json!(2)]);
+    }
+
+    #[test]
+    fn passes_messages_for_other_actions_through() {
+        let router = counter_router();
+        let (mut inspector, mut rx) = inspector();
+        inspector.ctx.action_id = "other".to_string();
+        let mut count = 0;
+
+        let message = json!({ "Increment": { "by": 2 } });
+        assert_eq!(
+            router.handle(&mut count, &inspector.session, &inspector, message.clone()),
+            Some(message)
+        );
+        assert_eq!(count, 0);
+        assert!(sent(&mut rx).is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_action_messages() {
+        let router = counter_router();
+        let (inspector, mut rx) = inspector();
+        let mut count = 0;
+
+        let message = json!({ "Decrement": { "by": 2 } });
+        assert!(
+            router
+                .handle(&mut count, &inspector.session, &inspector, message)
+                .is_none()
+        );
+        assert_eq!(count, 0);
+
+        let sent = sent(&mut rx);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["error"]["code"], RpcError::INVALID_MESSAGE);
+        assert!(
+            sent[0]["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("Decrement")
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use crate::protocol::InspectorContext;
+
+/// Envelope for RPC messages exchanged with the inspector through
+/// `SendToInspector` and `RecvFromInspector`
+///
+/// Requests can be sent in either direction, the receiver replies with
+/// a response using the same `id` as the request:
+///
+/// ```json
+/// { "rpc": "Request", "id": 1, "method": "getDevices", "params": { "filter": "all" } }
+/// { "rpc": "Response", "id": 1, "result": ["Device 1", "Device 2"] }
+/// { "rpc": "Response", "id": 1, "error": { "code": "MethodNotFound", "message": "unknown method getDevices" } }
+/// ```
+///
+/// Each side allocates the ids for its own requests, responses always
+/// carry either a `result` or an `error`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "rpc")]
+pub enum RpcMessage {
+    /// Request to invoke a method
+    Request {
+        /// ID used to correlate the response
+        id: u64,
+        /// Name of the method to invoke
+        method: String,
+        /// Parameters for the method
+        #[serde(default)]
+        params: serde_json::Value,
+    },
+
+    /// Response to a request
+    Response {
+        /// ID of the request this is a response to
+        id: u64,
+        /// Result of the method when successful
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        result: Option<serde_json::Value>,
+        /// Error when the method failed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<RpcError>,
+    },
+}
+
+impl RpcMessage {
+    /// Key present on all RPC envelopes, used to distinguish them
+    /// from other inspector messages
+    pub const KEY: &str = "rpc";
+
+    /// Creates a response from the outcome of a method
+    pub fn response(id: u64, outcome: Result<serde_json::Value, RpcError>) -> Self {
+        match outcome {
+            Ok(result) => RpcMessage::Response {
+                id,
+                result: Some(result),
+                error: None,
+            },
+            Err(error) => RpcMessage::Response {
+                id,
+                result: None,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+/// Structured error returned from an RPC method
+#[derive(Debug, Clone, Error, Serialize, Deserialize, PartialEq)]
+#[error("{code}: {message}")]
+pub struct RpcError {
+    /// Code identifying the type of error
+    pub code: String,
+    /// Human readable error message
+    pub message: String,
+    /// Additional error data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl RpcError {
+    /// The requested method is not registered
+    pub const METHOD_NOT_FOUND: &str = "MethodNotFound";
+    /// The parameters could not be deserialized for the method
+    pub const INVALID_PARAMS: &str = "InvalidParams";
+    /// The response could not be understood
+    pub const INVALID_RESPONSE: &str = "InvalidResponse";
+    /// The method failed for an internal reason
+    pub const INTERNAL: &str = "Internal";
+    /// The requested resource does not exist
+    pub const NOT_FOUND: &str = "NotFound";
+    /// A message from the inspector could not be understood
+    pub const INVALID_MESSAGE: &str = "InvalidMessage";
+    /// The RPC envelope of a request could not be understood
+    pub const INVALID_REQUEST: &str = "InvalidRequest";
+
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Attaches additional `data` to the error
+    pub fn with_data(mut self, data: serde_json::Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(Self::METHOD_NOT_FOUND, format!("unknown method {method}"))
+    }
+
+    pub fn invalid_params(cause: serde_json::Error) -> Self {
+        Self::new(Self::INVALID_PARAMS, cause.to_string())
+    }
+
+    pub fn invalid_request(cause: serde_json::Error) -> Self {
+        Self::new(Self::INVALID_REQUEST, cause.to_string())
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(Self::INTERNAL, message)
+    }
+}
+
+/// Outcome of an RPC call made to an inspector
+pub(crate) type RpcOutcome = Result<serde_json::Value, RpcError>;
+
+/// Store for pending RPC calls made from the plugin to inspectors
+#[derive(Default, Clone)]
+pub(crate) struct RpcCalls {
+    inner: Arc<RpcCallsInner>,
+}
+
+#[derive(Default)]
+struct RpcCallsInner {
+    /// Next request ID to use
+    next_id: AtomicU64,
+    /// Calls waiting for a response
+    pending: Mutex<HashMap<u64, PendingCall>>,
+}
+
+struct PendingCall {
+    /// Inspector the call was made to
+    ctx: InspectorContext,
+    /// Sender for the outcome of the call
+    tx: oneshot::Sender<RpcOutcome>,
+}
+
+impl RpcCalls {
+    /// Creates a new pending call to the inspector at `ctx`
+    pub fn start(&self, ctx: InspectorContext) -> (u64, oneshot::Receiver<RpcOutcome>) {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.inner
+            .pending
+            .lock()
+            .insert(id, PendingCall { ctx, tx });
+        (id, rx)
+    }
+
+    /// Completes the pending call with the provided `id`
+    pub fn resolve(&self, ctx: &InspectorContext, id: u64, outcome: RpcOutcome) {
+        let mut pending = self.inner.pending.lock();
+
+        // Only accept responses from the inspector the request was made to
+        if !pending.get(&id).is_some_and(|call| call.ctx.eq(ctx)) {
+            tracing::debug!(?id, "ignoring response for unknown rpc call");
+            return;
+        }
+
+        if let Some(call) = pending.remove(&id) {
+            _ = call.tx.send(outcome);
+        }
+    }
+
+    /// Checks whether the call `id` made to the inspector at `ctx` is
+    /// waiting for a response
+    pub fn is_pending(&self, ctx: &InspectorContext, id: u64) -> bool {
+        self.inner
+            .pending
+            .lock()
+            .get(&id)
+            .is_some_and(|call| call.ctx.eq(ctx))
+    }
+
+    /// Removes a call that was never sent or has timed out
+    pub fn cancel(&self, id: u64) {
+        self.inner.pending.lock().remove(&id);
+    }
+
+    /// Drops all pending calls made to the inspector at `ctx`
+    pub fn cancel_inspector(&self, ctx: &InspectorContext) {
+        self.inner.pending.lock().retain(|_, call| call.ctx.ne(ctx));
+    }
+
+    pub fn clear(&self) {
+        self.inner.pending.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn inspector_ctx() -> InspectorContext {
+        InspectorContext {
+            profile_id: Uuid::new_v4(),
+            folder_id: Uuid::new_v4(),
+            plugin_id: "plugin".to_string(),
+            action_id: "action".to_string(),
+            tile_id: Uuid::new_v4(),
+        }
+    }
+
+    #[test]
+    fn resolves_call_by_id() {
+        let calls = RpcCalls::default();
+        let ctx = inspector_ctx();
+        let (first, mut first_rx) = calls.start(ctx.clone());
+        let (second, mut second_rx) = calls.start(ctx.clone());
+        assert_ne!(first, second);
+
+        calls.resolve(&ctx, second, Ok(serde_json::json!(2)));
+        assert!(first_rx.try_recv().is_err());
+        assert_eq!(second_rx.try_recv().unwrap().unwrap(), 2);
+        assert!(!calls.is_pending(&ctx, second));
+
+        let error = RpcError::method_not_found("getDevices");
+        calls.resolve(&ctx, first, Err(error.clone()));
+        assert_eq!(first_rx.try_recv().unwrap().unwrap_err(), error);
+    }
+
+    #[test]
+    fn ignores_responses_from_other_inspectors() {
+        let calls = RpcCalls::default();
+        let ctx = inspector_ctx();
+        let (id, mut rx) = calls.start(ctx.clone());
+
+        calls.resolve(&inspector_ctx(), id, Ok(serde_json::Value::Null));
+        assert!(rx.try_recv().is_err());
+        assert!(calls.is_pending(&ctx, id));
+    }
+
+    #[test]
+    fn cancels_calls_to_closed_inspector() {
+        let calls = RpcCalls::default();
+        let closed = inspector_ctx();
+        let open = inspector_ctx();
+        let (_, mut closed_rx) = calls.start(closed.clone());
+        let (id, _open_rx) = calls.start(open.clone());
+
+        calls.cancel_inspector(&closed);
+        assert!(matches!(
+            closed_rx.try_recv(),
+            Err(oneshot::error::TryRecvError::Closed)
+        ));
+        assert!(calls.is_pending(&open, id));
+    }
+
+    #[test]
+    fn response_envelope_format() {
+        let response = RpcMessage::response(1, Ok(serde_json::json!(["Device 1"])));
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            serde_json::json!({ "rpc": "Response", "id": 1, "result": ["Device 1"] })
+        );
+
+        let response = RpcMessage::response(2, Err(RpcError::method_not_found("getDevices")));
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            serde_json::json!({
+                "rpc": "Response",
+                "id": 2,
+                "error": { "code": "MethodNotFound", "message": "unknown method getDevices" }
+            })
+        );
+    }
+}
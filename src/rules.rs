@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    protocol::{TileIcon, TileLabel},
+    style::Color,
+};
+
+/// Declarative set of styling rules that map a value to the
+/// appearance of a tile
+///
+/// Rules are plain serde types so they can be stored in the
+/// tile properties and edited from the inspector:
+///
+/// ```json
+/// {
+///     "rules": [
+///         { "when": "Above", "value": 90, "style": { "color": "#ff0000" } },
+///         { "when": "Above", "value": 70, "style": { "color": "#ffa500" } }
+///     ],
+///     "fallback": { "color": "#00ff00" }
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct StyleRules {
+    /// Rules to evaluate in order, the first matching rule is used
+    pub rules: Vec<StyleRule>,
+    /// Style to use when none of the rules match
+    pub fallback: Option<TileStyle>,
+}
+
+/// Single rule within a [StyleRules] set
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StyleRule {
+    /// Condition the value must meet for the rule to apply
+    #[serde(flatten)]
+    pub condition: StyleCondition,
+    /// Style to apply when the condition is met
+    pub style: TileStyle,
+}
+
+/// Condition for a [StyleRule]
+///
+/// Numeric conditions accept both JSON numbers and strings
+/// containing a number
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "when")]
+pub enum StyleCondition {
+    /// Value is greater than `value`
+    Above { value: f64 },
+    /// Value is greater than or equal to `value`
+    AtLeast { value: f64 },
+    /// Value is less than `value`
+    Below { value: f64 },
+    /// Value is less than or equal to `value`
+    AtMost { value: f64 },
+    /// Value is within the inclusive range `min` to `max`
+    Between { min: f64, max: f64 },
+    /// Value is exactly equal to `value`
+    Equals { value: serde_json::Value },
+    /// Always matches
+    Always,
+}
+
+/// Styling applied to a tile when a [StyleRule] matches
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TileStyle {
+    /// Label text color
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    /// Label outline color
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outline_color: Option<Color>,
+    /// Whether the label is bold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    /// Icon to use for the tile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<TileIcon>,
+}
+
+impl StyleRules {
+    /// Loads the rules stored under `key` in the provided tile
+    /// or plugin `properties`
+    ///
+    /// Returns [None] when the key is not present
+    pub fn from_properties(
+        properties: &serde_json::Value,
+        key: &str,
+    ) -> Result<Option<Self>, serde_json::Error> {
+        match properties.get(key) {
+            Some(value) if !value.is_null() => Self::deserialize(value).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Finds the style for the provided `value`, this is the style of the
+    /// first matching rule or the fallback style if no rules match
+    pub fn evaluate(&self, value: &serde_json::Value) -> Option<&TileStyle> {
+        self.rules
+            .iter()
+            .find(|rule| rule.condition.matches(value))
+            .map(|rule| &rule.style)
+            .or(self.fallback.as_ref())
+    }
+}
+
+impl StyleCondition {
+    /// Checks whether the provided `value` meets the condition
+    pub fn matches(&self, value: &serde_json::Value) -> bool {
+        match self {
+            StyleCondition::Always => true,
+            StyleCondition::Equals { value: expected } => expected.eq(value),
+            StyleCondition::Above { value: threshold } => {
+                as_number(value).is_some_and(|value| value > *threshold)
+            }
+            StyleCondition::AtLeast { value: threshold } => {
+                as_number(value).is_some_and(|value| value >= *threshold)
+            }
+            StyleCondition::Below { value: threshold } => {
+                as_number(value).is_some_and(|value| value < *threshold)
+            }
+            StyleCondition::AtMost { value: threshold } => {
+                as_number(value).is_some_and(|value| value <= *threshold)
+            }
+            StyleCondition::Between { min, max } => {
+                as_number(value).is_some_and(|value| value >= *min && value <= *max)
+            }
+        }
+    }
+}
+
+impl TileStyle {
+    /// Applies the style to the provided `label`, fields that are
+    /// not specified by the style are left unchanged
+    pub fn apply_to_label(&self, label: &mut TileLabel) {
+        if let Some(color) = self.color {
+            label.color = Some(color.to_string());
+        }
+
+        if let Some(outline_color) = self.outline_color {
+            label.outline_color = Some(outline_color.to_string());
+        }
+
+        if let Some(bold) = self.bold {
+            label.bold = Some(bold);
+        }
+    }
+}
+
+/// Converts a JSON value into a number for comparison
+fn as_number(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(value) => value.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn numeric_conditions_compare_numbers_and_strings() {
+        let above = StyleCondition::Above { value: 90.0 };
+        assert!(above.matches(&json!(91)));
+        assert!(above.matches(&json!(" 90.5 ")));
+        assert!(!above.matches(&json!(90)));
+        assert!(!above.matches(&json!("high")));
+        assert!(!above.matches(&json!(null)));
+        assert!(!above.matches(&json!(true)));
+
+        assert!(StyleCondition::AtLeast { value: 90.0 }.matches(&json!(90)));
+        assert!(StyleCondition::Below { value: 10.0 }.matches(&json!(-1.5)));
+        assert!(!StyleCondition::Below { value: 10.0 }.matches(&json!(10)));
+        assert!(StyleCondition::AtMost { value: 10.0 }.matches(&json!("10")));
+
+        let between = StyleCondition::Between {
+            min: 10.0,
+            max: 20.0,
+        };
+        assert!(between.matches(&json!(10)));
+        assert!(between.matches(&json!(20)));
+        assert!(!between.matches(&json!(20.1)));
+    }
+
+    #[test]
+    fn equals_and_always_conditions() {
+        let equals = StyleCondition::Equals { value: json!("on") };
+        assert!(equals.matches(&json!("on")));
+        assert!(!equals.matches(&json!("off")));
+        assert!(StyleCondition::Always.matches(&json!(null)));
+    }
+
+    #[test]
+    fn rules_use_flattened_when_tag() {
+        let rules: StyleRules = serde_json::from_value(json!({
+            "rules": [
+                { "when": "Above", "value": 90, "style": { "color": "#ff0000" } },
+                { "when": "Between", "min": 0, "max": 10, "style": { "bold": true } },
+                { "when": "Always", "style": {} }
+            ],
+            "fallback": { "color": "#00ff00" }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            rules.rules[0].condition,
+            StyleCondition::Above { value: 90.0 }
+        );
+        assert_eq!(
+            rules.rules[1].condition,
+            StyleCondition::Between {
+                min: 0.0,
+                max: 10.0,
+            }
+        );
+        assert_eq!(rules.rules[2].condition, StyleCondition::Always);
+
+        let rule = StyleRule {
+            condition: StyleCondition::AtMost { value: 5.0 },
+            style: TileStyle {
+                bold: Some(true),
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&rule).unwrap(),
+            json!({ "when": "AtMost", "value": 5.0, "style": { "bold": true } })
+        );
+    }
+
+    #[test]
+    fn evaluate_uses_first_match_then_fallback() {
+        let rules: StyleRules = serde_json::from_value(json!({
+            "rules": [
+                { "when": "Above", "value": 90, "style": { "bold": true } },
+                { "when": "Above", "value": 70, "style": { "bold": false } }
+            ],
+            "fallback": { "icon": { "type": "None" } }
+        }))
+        .unwrap();
+
+        assert_eq!(rules.evaluate(&json!(95)).unwrap().bold, Some(true));
+        assert_eq!(rules.evaluate(&json!(80)).unwrap().bold, Some(false));
+        assert_eq!(rules.evaluate(&json!(10)), rules.fallback.as_ref());
+        assert_eq!(StyleRules::default().evaluate(&json!(10)), None);
+    }
+
+    #[test]
+    fn loads_from_properties() {
+        let properties = json!({ "styles": { "rules": [] }, "empty": null });
+        assert!(
+            StyleRules::from_properties(&properties, "styles")
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            StyleRules::from_properties(&properties, "empty")
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            StyleRules::from_properties(&properties, "missing")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
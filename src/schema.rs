@@ -0,0 +1,553 @@
+use std::{collections::BTreeMap, fmt};
+
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::protocol::ActionId;
+
+/// JSON schemas for the properties of a plugin and its actions
+///
+/// Schemas are derived from the typed properties structs using
+/// [JsonSchema], set them on the session using
+/// [PluginSessionHandle::set_properties_schemas](crate::PluginSessionHandle::set_properties_schemas)
+/// to validate properties sent and received by the plugin
+///
+/// Validation supports the subset of JSON schema produced by the
+/// [JsonSchema] derive, `pattern` and `format` are not checked
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct PropertiesSchemas {
+    /// Schema for the plugin properties
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<Schema>,
+    /// Schemas for the tile properties of each action
+    pub actions: BTreeMap<ActionId, Schema>,
+}
+
+impl PropertiesSchemas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the plugin properties schema from the properties type `T`
+    pub fn plugin<T: JsonSchema>(mut self) -> Self {
+        self.plugin = Some(root_schema::<T>());
+        self
+    }
+
+    /// Sets the tile properties schema for `action_id` from the properties type `T`
+    pub fn action<T: JsonSchema>(mut self, action_id: impl Into<ActionId>) -> Self {
+        self.actions.insert(action_id.into(), root_schema::<T>());
+        self
+    }
+
+    /// Exports the schemas as a JSON object for use by tooling and inspectors
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// Validates the plugin `properties`, when `partial` is true only the
+    /// fields present in the `properties` are checked
+    pub fn validate_plugin(&self, properties: &Value, partial: bool) -> Result<(), SchemaErrors> {
+        match &self.plugin {
+            Some(schema) => validate(schema, properties, partial),
+            None => Ok(()),
+        }
+    }
+
+    /// Validates the tile `properties` for `action_id`, when `partial` is
+    /// true only the fields present in the `properties` are checked
+    pub fn validate_action(
+        &self,
+        action_id: &str,
+        properties: &Value,
+        partial: bool,
+    ) -> Result<(), SchemaErrors> {
+        match self.actions.get(action_id) {
+            Some(schema) => validate(schema, properties, partial),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Creates the root schema for `T`
+fn root_schema<T: JsonSchema>() -> Schema {
+    SchemaGenerator::default().into_root_schema_for::<T>()
+}
+
+/// Error for a single field that failed validation
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SchemaError {
+    /// JSON pointer to the field that failed validation
+    pub path: String,
+    /// Reason the field failed validation
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{path}: {}", self.message)
+    }
+}
+
+/// Collection of field errors from validating properties
+#[derive(Debug, Clone, Error, Serialize, PartialEq)]
+#[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+pub struct SchemaErrors(pub Vec<SchemaError>);
+
+/// Validates `value` against the root `schema`
+fn validate(schema: &Schema, value: &Value, partial: bool) -> Result<(), SchemaErrors> {
+    let root = schema.as_value();
+    let mut validator = Validator {
+        root,
+        errors: Vec::new(),
+    };
+
+    let mut path = String::new();
+
+    if partial {
+        validator.validate_partial(root, value, &mut path);
+    } else {
+        validator.validate(root, value, &mut path);
+    }
+
+    if validator.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemaErrors(validator.errors))
+    }
+}
+
+struct Validator<'a> {
+    /// Root schema used to resolve references
+    root: &'a Value,
+    /// Errors collected while validating
+    errors: Vec<SchemaError>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, path: &str, message: impl Into<String>) {
+        self.errors.push(SchemaError {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Resolves a local `$ref` to the referenced schema
+    fn resolve(&self, reference: &str) -> Option<&'a Value> {
+        let pointer = reference.strip_prefix('#')?;
+        self.root.pointer(pointer)
+    }
+
+    /// Validates only the fields present in an object value, used
+    /// for partial updates where the other fields are left unchanged
+    fn validate_partial(&mut self, schema: &'a Value, value: &Value, path: &mut String) {
+        let schema = match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => self.resolve(reference),
+            None => Some(schema),
+        };
+
+        let Some(schema) = schema.and_then(Value::as_object) else {
+            return;
+        };
+
+        let Some(object) = value.as_object() else {
+            self.error(path, "expected an object for a partial update");
+            return;
+        };
+
+        self.validate_properties(schema, object, path);
+    }
+
+    fn validate(&mut self, schema: &'a Value, value: &Value, path: &mut String) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                self.error(path, "value is not allowed");
+                return;
+            }
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            match self.resolve(reference) {
+                Some(resolved) => self.validate(resolved, value, path),
+                None => self.error(path, format!("unresolved reference {reference}")),
+            }
+        }
+
+        if let Some(types) = schema.get("type")
+            && !matches_type(types, value)
+        {
+            self.error(path, format!("expected type {types}"));
+            return;
+        }
+
+        if let Some(options) = schema.get("enum").and_then(Value::as_array)
+            && !options.contains(value)
+        {
+            self.error(path, "value is not one of the allowed values");
+        }
+
+        if let Some(expected) = schema.get("const")
+            && expected.ne(value)
+        {
+            self.error(path, format!("expected {expected}"));
+        }
+
+        self.validate_combinators(schema, value, path);
+
+        match value {
+            Value::Number(number) => {
+                if let Some(number) = number.as_f64() {
+                    self.validate_number(schema, number, path);
+                }
+            }
+            Value::String(string) => self.validate_string(schema, string, path),
+            Value::Array(items) => self.validate_array(schema, items, path),
+            Value::Object(object) => {
+                self.validate_required(schema, object, path);
+                self.validate_properties(schema, object, path);
+            }
+            _ => {}
+        }
+    }
+
+    fn validate_combinators(
+        &mut self,
+        schema: &'a Map<String, Value>,
+        value: &Value,
+        path: &mut String,
+    ) {
+        if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+            for schema in all_of {
+                self.validate(schema, value, path);
+            }
+        }
+
+        if let Some(any_of) = schema.get("anyOf").and_then(Value::as_array)
+            && !any_of.iter().any(|schema| self.is_valid(schema, value))
+        {
+            self.error(path, "value does not match any of the allowed schemas");
+        }
+
+        if let Some(one_of) = schema.get("oneOf").and_then(Value::as_array) {
+            let matches = one_of
+                .iter()
+                .filter(|schema| self.is_valid(schema, value))
+                .count();
+
+            if matches != 1 {
+                self.error(path, "value must match exactly one of the allowed schemas");
+            }
+        }
+    }
+
+    /// Checks if `value` matches `schema` without recording errors
+    fn is_valid(&self, schema: &'a Value, value: &Value) -> bool {
+        let mut validator = Validator {
+            root: self.root,
+            errors: Vec::new(),
+        };
+        validator.validate(schema, value, &mut String::new());
+        validator.errors.is_empty()
+    }
+
+    fn validate_number(&mut self, schema: &Map<String, Value>, number: f64, path: &str) {
+        let limit = |key: &str| schema.get(key).and_then(Value::as_f64);
+
+        if let Some(minimum) = limit("minimum")
+            && number < minimum
+        {
+            self.error(path, format!("must be at least {minimum}"));
+        }
+
+        if let Some(maximum) = limit("maximum")
+            && number > maximum
+        {
+            self.error(path, format!("must be at most {maximum}"));
+        }
+
+        if let Some(minimum) = limit("exclusiveMinimum")
+            && number <= minimum
+        {
+            self.error(path, format!("must be greater than {minimum}"));
+        }
+
+        if let Some(maximum) = limit("exclusiveMaximum")
+            && number >= maximum
+        {
+            self.error(path, format!("must be less than {maximum}"));
+        }
+    }
+
+    fn validate_string(&mut self, schema: &Map<String, Value>, string: &str, path: &str) {
+        let length = string.chars().count() as u64;
+
+        if let Some(min_length) = schema.get("minLength").and_then(Value::as_u64)
+            && length < min_length
+        {
+            self.error(path, format!("must be at least {min_length} characters"));
+        }
+
+        if let Some(max_length) = schema.get("maxLength").and_then(Value::as_u64)
+            && length > max_length
+        {
+            self.error(path, format!("must be at most {max_length} characters"));
+        }
+    }
+
+    fn validate_array(
+        &mut self,
+        schema: &'a Map<String, Value>,
+        items: &[Value],
+        path: &mut String,
+    ) {
+        let length = items.len() as u64;
+
+        if let Some(min_items) = schema.get("minItems").and_then(Value::as_u64)
+            && length < min_items
+        {
+            self.error(path, format!("must have at least {min_items} items"));
+        }
+
+        if let Some(max_items) = schema.get("maxItems").and_then(Value::as_u64)
+            && length > max_items
+        {
+            self.error(path, format!("must have at most {max_items} items"));
+        }
+
+        let prefix_items = schema
+            .get("prefixItems")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        for (index, item) in items.iter().enumerate() {
+            let item_schema = match prefix_items.get(index) {
+                Some(schema) => schema,
+                None => match schema.get("items") {
+                    Some(schema) => schema,
+                    None => continue,
+                },
+            };
+
+            with_segment(path, &index.to_string(), |path| {
+                self.validate(item_schema, item, path)
+            });
+        }
+    }
+
+    fn validate_required(
+        &mut self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &mut String,
+    ) {
+        let Some(required) = schema.get("required").and_then(Value::as_array) else {
+            return;
+        };
+
+        for key in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(key) {
+                with_segment(path, key, |path| self.error(path, "field is required"));
+            }
+        }
+    }
+
+    fn validate_properties(
+        &mut self,
+        schema: &'a Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &mut String,
+    ) {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties");
+
+        for (key, value) in object {
+            let property_schema = match properties.and_then(|properties| properties.get(key)) {
+                Some(schema) => schema,
+                None => match additional {
+                    Some(schema) => schema,
+                    None => continue,
+                },
+            };
+
+            if property_schema == &Value::Bool(false) {
+                with_segment(path, key, |path| self.error(path, "unknown field"));
+                continue;
+            }
+
+            with_segment(path, key, |path| {
+                self.validate(property_schema, value, path)
+            });
+        }
+    }
+}
+
+/// Runs `action` with `segment` appended to the JSON pointer `path`
+fn with_segment(path: &mut String, segment: &str, action: impl FnOnce(&mut String)) {
+    let length = path.len();
+    path.push('/');
+    path.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+    action(path);
+    path.truncate(length);
+}
+
+/// Checks if `value` matches the schema `types`, either
+/// a single type name or an array of type names
+fn matches_type(types: &Value, value: &Value) -> bool {
+    let matches = |name: &str| match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        // Floats such as `1.0` are rejected as they fail to deserialize into integer fields
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    };
+
+    match types {
+        Value::String(name) => matches(name),
+        Value::Array(names) => names.iter().filter_map(Value::as_str).any(matches),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use schemars::JsonSchema;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    enum Mode {
+        Fast,
+        Slow,
+    }
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct Threshold {
+        #[schemars(range(min = 1, max = 100))]
+        value: u32,
+    }
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct Properties {
+        label: String,
+        mode: Mode,
+        threshold: Threshold,
+        #[schemars(range(min = 0.5, max = 2.0))]
+        scale: Option<f64>,
+    }
+
+    fn schemas() -> PropertiesSchemas {
+        PropertiesSchemas::new().action::<Properties>("action")
+    }
+
+    /// Paths of the fields that failed validation
+    fn error_paths(result: Result<(), SchemaErrors>) -> Vec<String> {
+        match result {
+            Ok(()) => Vec::new(),
+            Err(SchemaErrors(errors)) => errors.into_iter().map(|error| error.path).collect(),
+        }
+    }
+
+    fn validate(properties: Value, partial: bool) -> Vec<String> {
+        error_paths(schemas().validate_action("action", &properties, partial))
+    }
+
+    #[test]
+    fn accepts_valid_properties() {
+        let properties = json!({
+            "label": "CPU",
+            "mode": "Fast",
+            "threshold": { "value": 90 },
+            "scale": 1.5
+        });
+        assert!(validate(properties, false).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_required_fields() {
+        let properties = json!({ "label": "CPU" });
+        assert_eq!(validate(properties, false), ["/mode", "/threshold"]);
+    }
+
+    #[test]
+    fn reports_mismatched_types() {
+        let properties = json!({
+            "label": 1,
+            "mode": "Fast",
+            "threshold": { "value": "90" }
+        });
+        assert_eq!(validate(properties, false), ["/label", "/threshold/value"]);
+    }
+
+    #[test]
+    fn integers_reject_floats() {
+        let properties = json!({
+            "label": "CPU",
+            "mode": "Fast",
+            "threshold": { "value": 1.0 }
+        });
+        assert_eq!(validate(properties, false), ["/threshold/value"]);
+    }
+
+    #[test]
+    fn reports_values_outside_enum() {
+        let properties = json!({
+            "label": "CPU",
+            "mode": "Medium",
+            "threshold": { "value": 90 }
+        });
+        assert_eq!(validate(properties, false), ["/mode"]);
+    }
+
+    #[test]
+    fn resolves_references() {
+        let schema = root_schema::<Properties>();
+        let threshold = &schema.as_value()["properties"]["threshold"];
+        assert!(threshold.get("$ref").is_some());
+
+        let properties = json!({ "threshold": { "value": 0 } });
+        assert_eq!(validate(properties, true), ["/threshold/value"]);
+    }
+
+    #[test]
+    fn reports_values_outside_range() {
+        let properties = json!({
+            "label": "CPU",
+            "mode": "Slow",
+            "threshold": { "value": 101 },
+            "scale": 0.25
+        });
+        assert_eq!(validate(properties, false), ["/scale", "/threshold/value"]);
+    }
+
+    #[test]
+    fn partial_only_checks_present_fields() {
+        assert!(validate(json!({ "label": "CPU" }), true).is_empty());
+        assert_eq!(validate(json!({ "mode": 1 }), true), ["/mode"]);
+        assert_eq!(validate(json!([]), true), [""]);
+    }
+
+    #[test]
+    fn skips_actions_without_schema() {
+        let result = schemas().validate_action("other", &json!({ "label": 1 }), false);
+        assert_eq!(result, Ok(()));
+    }
+}
@@ -1,17 +1,38 @@
-use std::task::{Poll, ready};
+use std::{
+    sync::Arc,
+    task::{Poll, ready},
+    time::Duration,
+};
 
-use futures_util::Stream;
-use serde::Serialize;
+use futures_util::{Stream, StreamExt};
+use parking_lot::Mutex;
+use serde::{Serialize, de::DeserializeOwned};
 use thiserror::Error;
-use tokio::sync::oneshot;
+use tokio::sync::{broadcast, oneshot};
+use uuid::Uuid;
 
 use crate::{
     DeviceId, DeviceIndicator,
+    ack::{AckCollector, Acknowledgement, PendingRequests},
+    deep_link::{DeepLinkUrl, parse_query},
+    display::{Display, DisplayStates},
+    events::{PluginEvent, event_stream},
+    host::{HostInfo, PROTOCOL_VERSION, SdkInfo},
+    inspector::{Inspector, OpenInspectors},
     protocol::{
-        ClientPluginMessage, InspectorContext, PluginId, ServerPluginMessage, TileIcon, TileId,
-        TileLabel, TileModel,
+        ActionId, ClientPluginMessage, DeepLinkContext, DisplayContext, InspectorContext, PluginId,
+        ServerPluginMessage, TileConfigUpdate, TileIcon, TileId, TileInteractionContext, TileLabel,
+        TileModel, TilePosition,
     },
+    protocol_error::{ProtocolError, ProtocolErrorCounters, ProtocolErrorStats},
+    rpc::{RpcCalls, RpcError, RpcMessage, RpcOutcome},
+    rules::StyleRules,
+    schema::{PropertiesSchemas, SchemaErrors},
+    style::StyleError,
     subscription::{Subscriber, Subscriptions},
+    text::{FittedLabel, LabelLayout},
+    theme::{Theme, Themes},
+    tiles::{KnownTiles, OwnershipMode},
     ws::{WsMessage, WsRx, WsTx},
 };
 
@@ -29,6 +50,56 @@ pub enum SessionError {
     /// Got an unexpected message from the server
     #[error("unexpected message")]
     UnexpectedMessage,
+
+    /// Tile styling was invalid and was not sent
+    #[error(transparent)]
+    InvalidStyle(#[from] StyleError),
+
+    /// RPC call to an inspector failed
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    /// Properties did not match the schema set with
+    /// [PluginSessionHandle::set_properties_schemas]
+    #[error("invalid properties: {0}")]
+    Schema(#[from] SchemaErrors),
+
+    /// Plugin has not registered with the server yet
+    #[error("plugin not registered")]
+    NotRegistered,
+
+    /// Timed out waiting for a response
+    #[error("timed out")]
+    Timeout,
+
+    /// Received a malformed message from the server
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+
+    /// Tile is not known to the plugin, see [OwnershipMode]
+    #[error("unknown tile {0}")]
+    UnknownTile(TileId),
+
+    /// Tile belongs to another plugin, see [OwnershipMode]
+    #[error("tile {tile_id} belongs to plugin {plugin_id}")]
+    NotOwned {
+        tile_id: TileId,
+        plugin_id: PluginId,
+    },
+
+    /// Host rejected the request
+    #[error("rejected by host ({code}): {message}")]
+    Rejected { code: String, message: String },
+
+    /// Operation requires a capability the host does not support,
+    /// see [PluginSessionHandle::host_info] and [HostInfo] for the
+    /// operations that check for capabilities
+    #[error("unsupported by host: requires {capability}")]
+    Unsupported { capability: String },
+
+    /// No tokio runtime was available to schedule delayed work on
+    #[error("no tokio runtime available")]
+    NoRuntime,
 }
 
 /// Handle to send messages on behalf of the plugin
@@ -36,27 +107,250 @@ pub enum SessionError {
 pub struct PluginSessionHandle {
     tx: WsTx,
     subscriptions: Subscriptions,
+    themes: Themes,
+    rpc_calls: RpcCalls,
+    inspectors: OpenInspectors,
+    tiles: KnownTiles,
+    schemas: Arc<Mutex<Option<Arc<PropertiesSchemas>>>>,
+    display_states: DisplayStates,
+    plugin_id: Arc<Mutex<Option<PluginId>>>,
+    events: Arc<Mutex<Option<broadcast::Sender<PluginEvent>>>>,
+    protocol_errors: ProtocolErrorCounters,
+    host: Arc<Mutex<Option<HostInfo>>>,
+    requests: PendingRequests,
+    /// Collects the requests sent within [PluginSessionHandle::with_ack]
+    acks: Option<AckCollector>,
+    ownership: Arc<Mutex<OwnershipMode>>,
 }
 
+/// Number of events buffered for each consumer of [PluginSessionHandle::events]
+const EVENTS_CAPACITY: usize = 256;
+
+/// Time to wait for a response to [PluginSessionHandle::call_inspector]
+pub const RPC_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
 impl PluginSessionHandle {
     pub(crate) fn new(tx: WsTx, subscriptions: Subscriptions) -> Self {
-        Self { tx, subscriptions }
+        Self {
+            tx,
+            subscriptions,
+            themes: Themes::default(),
+            rpc_calls: RpcCalls::default(),
+            inspectors: OpenInspectors::default(),
+            tiles: KnownTiles::default(),
+            schemas: Default::default(),
+            display_states: DisplayStates::default(),
+            plugin_id: Default::default(),
+            events: Arc::new(Mutex::new(Some(broadcast::channel(EVENTS_CAPACITY).0))),
+            protocol_errors: ProtocolErrorCounters::default(),
+            host: Default::default(),
+            requests: PendingRequests::default(),
+            acks: None,
+            ownership: Default::default(),
+        }
     }
 }
 
 impl PluginSessionHandle {
     /// Sends a message over the plugin websocket
     pub(crate) fn send_message(&self, msg: ClientPluginMessage) -> Result<(), SessionError> {
-        let msg = serde_json::to_string(&msg)?;
+        if let Some(acks) = &self.acks {
+            let request_id = self.requests.next_id();
+            acks.track(request_id);
+            return self.send_request(msg, request_id);
+        }
+
+        self.send_raw(msg)
+    }
+
+    /// Sends a message over the plugin websocket tagged with `request_id`,
+    /// the host includes the ID in its acknowledgement or error response
+    fn send_request(&self, msg: ClientPluginMessage, request_id: u64) -> Result<(), SessionError> {
+        let mut msg = serde_json::to_value(&msg)?;
+        if let Some(msg) = msg.as_object_mut() {
+            msg.insert("request_id".to_string(), request_id.into());
+        }
+
+        self.send_raw(msg)
+    }
+
+    /// Runs `action` and returns a future that resolves once the host has
+    /// acknowledged all the messages sent by `action`
+    ///
+    /// Allows waiting on the outcome of methods that would otherwise be
+    /// fire-and-forget, when the host rejects a message the future resolves
+    /// to [SessionError::Rejected]
+    ///
+    /// ```no_run
+    /// # use tilepad_plugin_sdk::{PluginSessionHandle, SessionError, TileIcon, TileId};
+    /// # async fn example(session: PluginSessionHandle, tile_id: TileId) -> Result<(), SessionError> {
+    /// session
+    ///     .with_ack(|session| session.set_tile_icon(tile_id, TileIcon::None))?
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Requires the host to support [HostInfo::ACKNOWLEDGEMENTS], otherwise
+    /// [SessionError::Unsupported] is returned
+    pub fn with_ack<F>(&self, action: F) -> Result<Acknowledgement, SessionError>
+    where
+        F: FnOnce(&PluginSessionHandle) -> Result<(), SessionError>,
+    {
+        self.require(HostInfo::ACKNOWLEDGEMENTS)?;
+
+        let acks = AckCollector::new(self.requests.clone());
+        let session = PluginSessionHandle {
+            acks: Some(acks.clone()),
+            ..self.clone()
+        };
+
+        action(&session)?;
+        Ok(acks.into_acknowledgement())
+    }
+
+    /// Sends the request `msg` and subscribes to the first response matching
+    /// `filter`
+    ///
+    /// When the host supports [HostInfo::ACKNOWLEDGEMENTS] the request is tagged
+    /// with a request ID so an error from the host for the request is also
+    /// received as the response
+    fn send_with_response<F>(
+        &self,
+        msg: ClientPluginMessage,
+        filter: F,
+    ) -> Result<oneshot::Receiver<ServerPluginMessage>, SessionError>
+    where
+        F: Fn(&ServerPluginMessage) -> bool + Send + Sync + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+
+        if !self.supports(HostInfo::ACKNOWLEDGEMENTS) {
+            self.subscriptions.add(Subscriber::new(filter, tx));
+            self.send_message(msg)?;
+            return Ok(rx);
+        }
+
+        let request_id = self.requests.next_id();
+        self.subscriptions.add(Subscriber::new(
+            move |msg| match msg {
+                ServerPluginMessage::Error {
+                    request_id: Some(other_id),
+                    ..
+                } => request_id.eq(other_id),
+                msg => filter(msg),
+            },
+            tx,
+        ));
+
+        self.send_request(msg, request_id)?;
+        Ok(rx)
+    }
+
+    /// Records the state from a message received from the server
+    pub(crate) fn observe(&self, msg: &ServerPluginMessage) {
+        self.tiles.apply(msg);
+        self.themes.apply(msg);
+        self.requests.apply(msg);
+
+        if let ServerPluginMessage::Registered { host, .. } = msg {
+            *self.host.lock() = Some(host.clone());
+        }
+
+        if let Some((ctx, message)) = self.display_states.replay(msg)
+            && let Err(cause) =
+                self.send_message(ClientPluginMessage::SendToDisplay { ctx, message })
+        {
+            tracing::error!(?cause, "failed to resend display state");
+        }
+    }
+
+    /// Publishes a message from the server to the consumers of [PluginSessionHandle::events]
+    pub(crate) fn publish_event(&self, msg: &ServerPluginMessage) {
+        let events = &*self.events.lock();
+        if let Some(events) = events
+            && events.receiver_count() > 0
+        {
+            _ = events.send(msg.clone().into());
+        }
+    }
+
+    /// Clears the state recorded from messages received from the server,
+    /// ending any event streams
+    pub(crate) fn clear_observed(&self) {
+        self.tiles.clear();
+        self.display_states.clear();
+        self.themes.clear();
+        self.requests.clear();
+        self.events.lock().take();
+    }
+
+    /// Sends a raw JSON `message` to the server
+    ///
+    /// Allows using messages added to Tilepad that this version of the SDK
+    /// does not provide methods for, the message must include a `type` field.
+    /// No validation is done on the message
+    pub fn send_raw<M>(&self, message: M) -> Result<(), SessionError>
+    where
+        M: Serialize,
+    {
+        let msg = serde_json::to_string(&message)?;
         let message = WsMessage::text(msg);
         tracing::debug!(?message, "sending message to server");
         self.tx.send(message).map_err(|_| SessionError::Closed)?;
         Ok(())
     }
 
+    /// Records a malformed message received from the server
+    pub(crate) fn record_protocol_error(&self, error: &ProtocolError) {
+        self.protocol_errors.record(error);
+    }
+
+    /// Closes the connection to the server
+    pub(crate) fn disconnect(&self) {
+        _ = self.tx.send(WsMessage::Close(None));
+    }
+
+    /// Details about the host received when the plugin registered,
+    /// [None] until the plugin has registered
+    pub fn host_info(&self) -> Option<HostInfo> {
+        self.host.lock().clone()
+    }
+
+    /// Checks if the host supports the `capability`, see [HostInfo]
+    pub fn supports(&self, capability: &str) -> bool {
+        self.host
+            .lock()
+            .as_ref()
+            .is_some_and(|host| host.supports(capability))
+    }
+
+    /// Checks the host supports the `capability` before using
+    /// an operation that requires it
+    fn require(&self, capability: &str) -> Result<(), SessionError> {
+        if self.supports(capability) {
+            Ok(())
+        } else {
+            Err(SessionError::Unsupported {
+                capability: capability.to_string(),
+            })
+        }
+    }
+
+    /// Counts of the malformed messages received from the server that
+    /// were rejected, see [ProtocolError]
+    pub fn protocol_error_stats(&self) -> ProtocolErrorStats {
+        self.protocol_errors.get()
+    }
+
     /// Registers the plugin with the plugin server
     pub(crate) fn register(&self, plugin_id: PluginId) -> Result<(), SessionError> {
-        self.send_message(ClientPluginMessage::RegisterPlugin { plugin_id })?;
+        *self.plugin_id.lock() = Some(plugin_id.clone());
+        self.send_message(ClientPluginMessage::RegisterPlugin {
+            plugin_id,
+            sdk: SdkInfo::current(),
+            protocol_version: PROTOCOL_VERSION,
+        })?;
         Ok(())
     }
 
@@ -69,20 +363,15 @@ impl PluginSessionHandle {
     /// Requests the current properties from tilepad waiting until
     /// the response is retrieved and returns that
     pub async fn get_properties(&self) -> Result<serde_json::Value, SessionError> {
-        let (tx, rx) = oneshot::channel();
-
-        self.subscriptions.add(Subscriber::new(
-            |msg| matches!(msg, ServerPluginMessage::Properties { .. }),
-            tx,
-        ));
-
-        self.request_properties()?;
+        let rx = self.send_with_response(ClientPluginMessage::GetProperties {}, |msg| {
+            matches!(msg, ServerPluginMessage::Properties { .. })
+        })?;
 
         // Wait for the response message
         let msg = rx.await.map_err(|_| SessionError::Closed)?;
         let msg = match msg {
             ServerPluginMessage::Properties { properties } => properties,
-            _ => return Err(SessionError::UnexpectedMessage),
+            msg => return Err(unexpected_response(msg)),
         };
 
         Ok(msg)
@@ -99,6 +388,7 @@ impl PluginSessionHandle {
         T: Serialize,
     {
         let properties = serde_json::to_value(properties)?;
+        self.validate_plugin_properties(&properties, false)?;
         self.send_message(ClientPluginMessage::SetProperties {
             properties,
             partial: false,
@@ -116,12 +406,71 @@ impl PluginSessionHandle {
         T: Serialize,
     {
         let properties = serde_json::to_value(properties)?;
+        self.validate_plugin_properties(&properties, true)?;
         self.send_message(ClientPluginMessage::SetProperties {
             properties,
             partial: true,
         })
     }
 
+    /// Sets the schemas used to validate the plugin and tile properties
+    ///
+    /// Once set the properties passed to [PluginSessionHandle::set_properties],
+    /// [PluginSessionHandle::set_tile_properties] and their partial variants are
+    /// validated before sending, failing with [SessionError::Schema]. Properties
+    /// received from Tilepad are validated and reported to
+    /// [Plugin::on_invalid_properties](crate::Plugin::on_invalid_properties)
+    ///
+    /// Tile properties can only be validated for tiles where the action
+    /// is known from a message previously received from Tilepad, the
+    /// properties of other tiles are sent without validation
+    pub fn set_properties_schemas(&self, schemas: PropertiesSchemas) {
+        *self.schemas.lock() = Some(Arc::new(schemas));
+    }
+
+    /// Gets the schemas set with [PluginSessionHandle::set_properties_schemas]
+    pub fn properties_schemas(&self) -> Option<Arc<PropertiesSchemas>> {
+        self.schemas.lock().clone()
+    }
+
+    /// Validates plugin `properties` against the current schemas
+    pub(crate) fn validate_plugin_properties(
+        &self,
+        properties: &serde_json::Value,
+        partial: bool,
+    ) -> Result<(), SchemaErrors> {
+        match self.properties_schemas() {
+            Some(schemas) => schemas.validate_plugin(properties, partial),
+            None => Ok(()),
+        }
+    }
+
+    /// Validates the `properties` for a tile against the schema of its action
+    ///
+    /// Validation is skipped when the action of the tile is not known, such as
+    /// for tiles that have not been seen or are no longer visible
+    pub(crate) fn validate_tile_properties(
+        &self,
+        tile_id: TileId,
+        properties: &serde_json::Value,
+        partial: bool,
+    ) -> Result<(), SchemaErrors> {
+        let Some(schemas) = self.properties_schemas() else {
+            return Ok(());
+        };
+
+        if schemas.actions.is_empty() {
+            return Ok(());
+        }
+
+        let Some(tile) = self.tiles.get(&tile_id) else {
+            tracing::debug!(%tile_id, "action of tile is unknown, skipping properties validation");
+            return Ok(());
+        };
+
+        schemas.validate_action(&tile.action_id, properties, partial)
+    }
+
     /// Requests the specified tile properties from the server
     pub fn request_tile_properties(&self, tile_id: TileId) -> Result<(), SessionError> {
         self.send_message(ClientPluginMessage::GetTileProperties { tile_id })?;
@@ -134,25 +483,21 @@ impl PluginSessionHandle {
         &self,
         tile_id: TileId,
     ) -> Result<serde_json::Value, SessionError> {
-        let (tx, rx) = oneshot::channel();
-
-        self.subscriptions.add(Subscriber::new(
+        let rx = self.send_with_response(
+            ClientPluginMessage::GetTileProperties { tile_id },
             move |msg| match msg {
                 ServerPluginMessage::TileProperties {
                     tile_id: other_id, ..
                 } => other_id.eq(&tile_id),
                 _ => false,
             },
-            tx,
-        ));
-
-        self.request_tile_properties(tile_id)?;
+        )?;
 
         // Wait for the response message
         let msg = rx.await.map_err(|_| SessionError::Closed)?;
         let msg = match msg {
             ServerPluginMessage::TileProperties { properties, .. } => properties,
-            _ => return Err(SessionError::UnexpectedMessage),
+            msg => return Err(unexpected_response(msg)),
         };
 
         Ok(msg)
@@ -167,20 +512,15 @@ impl PluginSessionHandle {
     /// Requests the current properties for a tile from tilepad waiting until
     /// the response is retrieved and returns that
     pub async fn get_visible_tiles(&self) -> Result<Vec<TileModel>, SessionError> {
-        let (tx, rx) = oneshot::channel();
-
-        self.subscriptions.add(Subscriber::new(
-            move |msg| matches!(msg, ServerPluginMessage::VisibleTiles { .. }),
-            tx,
-        ));
-
-        self.request_visible_tiles()?;
+        let rx = self.send_with_response(ClientPluginMessage::GetVisibleTiles, |msg| {
+            matches!(msg, ServerPluginMessage::VisibleTiles { .. })
+        })?;
 
         // Wait for the response message
         let msg = rx.await.map_err(|_| SessionError::Closed)?;
         let msg = match msg {
             ServerPluginMessage::VisibleTiles { tiles } => tiles,
-            _ => return Err(SessionError::UnexpectedMessage),
+            msg => return Err(unexpected_response(msg)),
         };
 
         Ok(msg)
@@ -206,6 +546,39 @@ impl PluginSessionHandle {
         })
     }
 
+    /// Sets how tile mutations are checked against the tiles known
+    /// to the plugin before they are sent, see [OwnershipMode]
+    pub fn set_ownership_mode(&self, mode: OwnershipMode) {
+        *self.ownership.lock() = mode;
+    }
+
+    /// Checks the plugin owns `tile_id` before it is mutated,
+    /// based on the current [OwnershipMode]
+    fn check_ownership(&self, tile_id: TileId) -> Result<(), SessionError> {
+        let mode = *self.ownership.lock();
+        if mode == OwnershipMode::Off {
+            return Ok(());
+        }
+
+        let error = match self.tiles.get(&tile_id) {
+            None => SessionError::UnknownTile(tile_id),
+            Some(tile) => match self.plugin_id() {
+                Some(plugin_id) if plugin_id != tile.plugin_id => SessionError::NotOwned {
+                    tile_id,
+                    plugin_id: tile.plugin_id,
+                },
+                _ => return Ok(()),
+            },
+        };
+
+        if mode == OwnershipMode::Enforce {
+            return Err(error);
+        }
+
+        tracing::warn!(%error, "updating tile not owned by the plugin");
+        Ok(())
+    }
+
     /// Sets the properties for the specified tile
     ///
     /// You can only update tiles that are using an action
@@ -219,7 +592,9 @@ impl PluginSessionHandle {
     where
         T: Serialize,
     {
+        self.check_ownership(tile_id)?;
         let properties = serde_json::to_value(properties)?;
+        self.validate_tile_properties(tile_id, &properties, false)?;
         self.send_message(ClientPluginMessage::SetTileProperties {
             tile_id,
             properties,
@@ -244,7 +619,9 @@ impl PluginSessionHandle {
     where
         T: Serialize,
     {
+        self.check_ownership(tile_id)?;
         let properties = serde_json::to_value(properties)?;
+        self.validate_tile_properties(tile_id, &properties, true)?;
         self.send_message(ClientPluginMessage::SetTileProperties {
             tile_id,
             properties,
@@ -254,9 +631,24 @@ impl PluginSessionHandle {
 
     /// Sets the icon for a specific tile
     ///
+    /// When the current theme specifies all of the icon styling and the host
+    /// supports [HostInfo::TILE_CONFIG] the theme icon options are sent
+    /// along with the icon
+    ///
     /// You can only update tiles that are using an action
     /// from your plugin
     pub fn set_tile_icon(&self, tile_id: TileId, icon: TileIcon) -> Result<(), SessionError> {
+        self.check_ownership(tile_id)?;
+
+        if self.supports(HostInfo::TILE_CONFIG)
+            && let Some(icon_options) = self.themes.resolve(&tile_id).icon_options(None)
+        {
+            let config = TileConfigUpdate::default()
+                .icon(icon)
+                .icon_options(icon_options);
+            return self.send_message(ClientPluginMessage::SetTileConfig { tile_id, config });
+        }
+
         self.send_message(ClientPluginMessage::SetTileIcon { tile_id, icon })
     }
 
@@ -264,10 +656,177 @@ impl PluginSessionHandle {
     ///
     /// You can only update tiles that are using an action
     /// from your plugin
+    ///
+    /// The current theme is applied to the label, see [PluginSessionHandle::set_theme].
+    /// The label is sent as is, use [TileLabel::builder] to validate the styling
     pub fn set_tile_label(&self, tile_id: TileId, label: TileLabel) -> Result<(), SessionError> {
+        self.check_ownership(tile_id)?;
+        let label = self.themes.apply_label(tile_id, label);
         self.send_message(ClientPluginMessage::SetTileLabel { tile_id, label })
     }
 
+    /// Updates the icon, icon options and label for a specific tile
+    /// in a single message, parts of the config that are [None] are
+    /// left unchanged
+    ///
+    /// The current theme is applied to the label, the config is sent as is,
+    /// use the [TileLabel::builder] and [TileIconOptions::builder](crate::TileIconOptions::builder)
+    /// to validate the styling. When no icon options are provided the theme
+    /// icon options are sent if the theme specifies all of the icon styling
+    ///
+    /// You can only update tiles that are using an action
+    /// from your plugin
+    ///
+    /// Requires the host to support [HostInfo::TILE_CONFIG], otherwise
+    /// [SessionError::Unsupported] is returned
+    pub fn set_tile_config(
+        &self,
+        tile_id: TileId,
+        mut config: TileConfigUpdate,
+    ) -> Result<(), SessionError> {
+        self.require(HostInfo::TILE_CONFIG)?;
+        self.check_ownership(tile_id)?;
+
+        if let Some(label) = config.label.take() {
+            config.label = Some(self.themes.apply_label(tile_id, label));
+        }
+
+        if config.icon_options.is_none() {
+            config.icon_options = self.themes.resolve(&tile_id).icon_options(None);
+        }
+
+        self.send_message(ClientPluginMessage::SetTileConfig { tile_id, config })
+    }
+
+    /// Sets the label for a specific tile, fitting `text` within
+    /// the tile using the default [LabelLayout]
+    ///
+    /// The text is wrapped, shrunk or truncated based on the size of the
+    /// tile from `position` and the font size of the `label` (or the
+    /// current theme), see [LabelLayout::fit]
+    ///
+    /// You can only update tiles that are using an action
+    /// from your plugin
+    pub fn set_tile_label_fitted(
+        &self,
+        tile_id: TileId,
+        position: &TilePosition,
+        mut label: TileLabel,
+        text: &str,
+    ) -> Result<FittedLabel, SessionError> {
+        let mut themed = label.clone();
+        self.themes.resolve(&tile_id).apply_to_label(&mut themed);
+
+        let fitted = LabelLayout::default().fit(text, position, &themed);
+        fitted.apply_to_label(&mut label);
+
+        self.set_tile_label(tile_id, label)?;
+        Ok(fitted)
+    }
+
+    /// Applies the style from `rules` matching `value` to a specific tile
+    ///
+    /// The matched style is merged into the provided `label` before it is
+    /// sent, when the style specifies an icon the tile icon is updated
+    /// in the same message. When no rule matches the `label` is sent unchanged
+    ///
+    /// You can only update tiles that are using an action
+    /// from your plugin
+    pub fn apply_style_rules(
+        &self,
+        tile_id: TileId,
+        mut label: TileLabel,
+        rules: &StyleRules,
+        value: &serde_json::Value,
+    ) -> Result<(), SessionError> {
+        let mut config = TileConfigUpdate::default();
+
+        if let Some(style) = rules.evaluate(value) {
+            style.apply_to_label(&mut label);
+            config.icon = style.icon.clone();
+        }
+
+        self.update_tile(tile_id, config.label(label))
+    }
+
+    /// Updates a tile using [PluginSessionHandle::set_tile_config] when the
+    /// host supports it, otherwise falls back to setting the icon and label
+    /// in separate messages without the icon options
+    fn update_tile(&self, tile_id: TileId, config: TileConfigUpdate) -> Result<(), SessionError> {
+        if self.supports(HostInfo::TILE_CONFIG) {
+            return self.set_tile_config(tile_id, config);
+        }
+
+        if let Some(icon) = config.icon {
+            self.set_tile_icon(tile_id, icon)?;
+        }
+
+        if let Some(label) = config.label {
+            self.set_tile_label(tile_id, label)?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets the theme currently applied to all tiles
+    pub fn theme(&self) -> Theme {
+        self.themes.theme()
+    }
+
+    /// Sets the theme applied to all tile labels sent by the plugin
+    ///
+    /// This only affects labels sent after the theme is set, use
+    /// [PluginSessionHandle::switch_theme] to also repaint the
+    /// currently visible tiles
+    pub fn set_theme(&self, theme: Theme) {
+        self.themes.set_theme(theme);
+    }
+
+    /// Sets a theme override for a specific tile, fields specified
+    /// by the override take priority over the plugin theme
+    pub fn set_tile_theme(&self, tile_id: TileId, theme: Theme) {
+        self.themes.set_override(tile_id, theme);
+    }
+
+    /// Removes the theme override for a specific tile
+    ///
+    /// Overrides are kept while the tile is hidden, they are only
+    /// removed by this or when the session ends
+    pub fn clear_tile_theme(&self, tile_id: TileId) {
+        self.themes.remove_override(&tile_id);
+    }
+
+    /// Sets the theme applied to all tiles and repaints
+    /// the currently visible tiles using the new theme
+    pub async fn switch_theme(&self, theme: Theme) -> Result<(), SessionError> {
+        self.set_theme(theme);
+        self.repaint_visible_tiles().await
+    }
+
+    /// Re-sends the labels and icon options of all currently visible
+    /// tiles applying the current theme
+    ///
+    /// Tiles that have had a label set by the plugin are repainted
+    /// from the label the plugin last provided, otherwise the current
+    /// label of the tile is used without the styling from the current
+    /// or previous theme
+    pub async fn repaint_visible_tiles(&self) -> Result<(), SessionError> {
+        let tiles = self.get_visible_tiles().await?;
+
+        for tile in tiles {
+            let label = self.themes.base_label(&tile.id, tile.config.label);
+            let mut config = TileConfigUpdate::default().label(label);
+            config.icon_options = self
+                .themes
+                .resolve(&tile.id)
+                .icon_options(tile.config.icon_options.as_ref());
+
+            self.update_tile(tile.id, config)?;
+        }
+
+        Ok(())
+    }
+
     /// Sends a message to the plugin inspector UI at the provided
     /// inspector context
     pub fn send_to_inspector<T>(&self, ctx: InspectorContext, msg: T) -> Result<(), SessionError>
@@ -278,6 +837,355 @@ impl PluginSessionHandle {
         self.send_message(ClientPluginMessage::SendToInspector { ctx, message })
     }
 
+    /// Gets all the inspectors that are currently open
+    pub fn open_inspectors(&self) -> Vec<Inspector> {
+        self.inspectors
+            .all()
+            .into_iter()
+            .map(|ctx| Inspector {
+                ctx,
+                session: self.clone(),
+            })
+            .collect()
+    }
+
+    /// Gets the inspector that is currently open for a specific tile
+    pub fn inspector_for_tile(&self, tile_id: TileId) -> Option<Inspector> {
+        self.inspectors.get(&tile_id).map(|ctx| Inspector {
+            ctx,
+            session: self.clone(),
+        })
+    }
+
+    /// Sends a message to all the currently open inspectors for
+    /// tiles using the action `action_id`
+    ///
+    /// Returns the number of inspectors the message was sent to
+    pub fn broadcast_to_inspectors<T>(&self, action_id: &str, msg: T) -> Result<usize, SessionError>
+    where
+        T: Serialize,
+    {
+        let message = serde_json::to_value(msg)?;
+        let mut count = 0;
+
+        for ctx in self.inspectors.all() {
+            if ctx.action_id != action_id {
+                continue;
+            }
+
+            self.send_message(ClientPluginMessage::SendToInspector {
+                ctx,
+                message: message.clone(),
+            })?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Tracks an inspector as open
+    pub(crate) fn inspector_opened(&self, ctx: InspectorContext) {
+        self.inspectors.open(ctx);
+    }
+
+    /// Tracks an inspector as closed, dropping any pending RPC calls made to it
+    pub(crate) fn inspector_closed(&self, ctx: &InspectorContext) {
+        self.inspectors.close(ctx);
+        self.rpc_calls.cancel_inspector(ctx);
+    }
+
+    /// Clears all tracked inspectors and pending RPC calls
+    pub(crate) fn clear_inspectors(&self) {
+        self.inspectors.clear();
+        self.rpc_calls.clear();
+    }
+
+    /// Calls the RPC `method` on the plugin inspector UI at the
+    /// provided inspector context, waiting for the response
+    ///
+    /// The call fails with [SessionError::Rpc] when the inspector
+    /// responds with an error, [SessionError::Closed] if the
+    /// inspector is closed before responding and [SessionError::Timeout]
+    /// when no response is received within [RPC_CALL_TIMEOUT]
+    pub async fn call_inspector<Params, R>(
+        &self,
+        ctx: InspectorContext,
+        method: impl Into<String>,
+        params: Params,
+    ) -> Result<R, SessionError>
+    where
+        Params: Serialize,
+        R: DeserializeOwned,
+    {
+        self.call_inspector_timeout(ctx, method, params, RPC_CALL_TIMEOUT)
+            .await
+    }
+
+    /// Calls the RPC `method` on the plugin inspector UI at the provided
+    /// inspector context, waiting up to `timeout` for the response
+    ///
+    /// See [PluginSessionHandle::call_inspector]
+    pub async fn call_inspector_timeout<Params, R>(
+        &self,
+        ctx: InspectorContext,
+        method: impl Into<String>,
+        params: Params,
+        timeout: Duration,
+    ) -> Result<R, SessionError>
+    where
+        Params: Serialize,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params)?;
+        let (id, rx) = self.rpc_calls.start(ctx.clone());
+
+        let request = RpcMessage::Request {
+            id,
+            method: method.into(),
+            params,
+        };
+
+        if let Err(cause) = self.send_to_inspector(ctx, request) {
+            self.rpc_calls.cancel(id);
+            return Err(cause);
+        }
+
+        // Wait for the response message
+        let result = match tokio::time::timeout(timeout, rx).await {
+            Ok(result) => result.map_err(|_| SessionError::Closed)??,
+            Err(_) => {
+                self.rpc_calls.cancel(id);
+                return Err(SessionError::Timeout);
+            }
+        };
+        let result = serde_json::from_value(result)
+            .map_err(|cause| RpcError::new(RpcError::INVALID_RESPONSE, cause.to_string()))?;
+
+        Ok(result)
+    }
+
+    /// Checks whether the RPC call `id` made to the inspector at `ctx`
+    /// is waiting for a response
+    pub(crate) fn has_rpc_call(&self, ctx: &InspectorContext, id: u64) -> bool {
+        self.rpc_calls.is_pending(ctx, id)
+    }
+
+    /// Completes a pending RPC call made to the inspector at `ctx`
+    pub(crate) fn resolve_rpc_call(&self, ctx: &InspectorContext, id: u64, outcome: RpcOutcome) {
+        self.rpc_calls.resolve(ctx, id, outcome);
+    }
+
+    /// Gets the displays for tiles using the action `action_id` that are
+    /// currently visible, optionally only including the displays on `device_id`
+    ///
+    /// Displays are found from the latest tiles Tilepad has sent
+    /// for each device, see [Plugin::on_device_tiles](crate::Plugin::on_device_tiles).
+    /// Only tiles using an action from this plugin are included, no displays
+    /// are found before the plugin has registered
+    pub fn visible_displays(&self, action_id: &str, device_id: Option<DeviceId>) -> Vec<Display> {
+        let Some(plugin_id) = self.plugin_id() else {
+            return Vec::new();
+        };
+
+        self.tiles
+            .displays(&plugin_id, action_id, device_id)
+            .into_iter()
+            .map(|ctx| Display {
+                ctx,
+                session: self.clone(),
+            })
+            .collect()
+    }
+
+    /// Sends a message to all the currently visible displays for tiles
+    /// using the action `action_id`, optionally only sending to the
+    /// displays on `device_id`
+    ///
+    /// Returns the number of displays the message was sent to
+    pub fn broadcast_to_displays<T>(
+        &self,
+        action_id: &str,
+        device_id: Option<DeviceId>,
+        msg: T,
+    ) -> Result<usize, SessionError>
+    where
+        T: Serialize,
+    {
+        let message = serde_json::to_value(msg)?;
+        let displays = self.visible_displays(action_id, device_id);
+        let count = displays.len();
+
+        for display in displays {
+            display.send(&message)?;
+        }
+
+        Ok(count)
+    }
+
+    /// Sets the latest state for the display at the provided display
+    /// context and sends it to the display
+    ///
+    /// The state is resent automatically when the display reports it is
+    /// ready by sending a message with the `display` key set to
+    /// [DISPLAY_READY_VALUE](crate::DISPLAY_READY_VALUE). The state is kept
+    /// while the display is hidden, such as when another folder is shown,
+    /// until [PluginSessionHandle::clear_display_state] is called or the
+    /// session ends
+    pub fn set_display_state<T>(&self, ctx: DisplayContext, state: T) -> Result<(), SessionError>
+    where
+        T: Serialize,
+    {
+        let message = serde_json::to_value(state)?;
+        self.display_states.set(ctx.clone(), message.clone());
+        self.send_message(ClientPluginMessage::SendToDisplay { ctx, message })
+    }
+
+    /// Removes the stored state for the display at the provided display
+    /// context, the display will no longer have its state resent
+    pub fn clear_display_state(&self, ctx: &DisplayContext) {
+        self.display_states.remove(ctx);
+    }
+
+    /// ID the plugin registered with
+    pub fn plugin_id(&self) -> Option<PluginId> {
+        self.plugin_id.lock().clone()
+    }
+
+    /// Creates a builder for a deep link URL that routes back to this
+    /// plugin, see [DeepLinkUrl]
+    pub fn deep_link_url(&self) -> Result<DeepLinkUrl, SessionError> {
+        let plugin_id = self.plugin_id().ok_or(SessionError::NotRegistered)?;
+        Ok(DeepLinkUrl::new(plugin_id))
+    }
+
+    /// Stream of the events received from the server
+    ///
+    /// Each call creates a new independent stream, events are received
+    /// from the point the stream is created. Consumers that fall too far
+    /// behind skip the events they missed. The stream ends when the
+    /// session is closed
+    pub fn events(&self) -> impl Stream<Item = PluginEvent> + Send + 'static {
+        let rx = match &*self.events.lock() {
+            Some(events) => events.subscribe(),
+            // Session has closed, create an already closed receiver
+            None => broadcast::channel(1).1,
+        };
+
+        event_stream(rx)
+    }
+
+    /// Stream of the clicks on tiles using the action `action_id`
+    pub fn tile_clicks(
+        &self,
+        action_id: impl Into<ActionId>,
+    ) -> impl Stream<Item = (TileInteractionContext, serde_json::Value)> + Send + 'static {
+        let action_id = action_id.into();
+
+        self.events().filter_map(move |event| {
+            let click = match event {
+                PluginEvent::TileClicked { ctx, properties } if ctx.action_id == action_id => {
+                    Some((ctx, properties))
+                }
+                _ => None,
+            };
+
+            std::future::ready(click)
+        })
+    }
+
+    /// Waits for the next event matching the `predicate`
+    ///
+    /// Events are captured from when this is called rather than when the
+    /// returned future is first polled, so the future can be created before
+    /// sending a message that triggers the event
+    pub fn next_event_matching<F>(
+        &self,
+        mut predicate: F,
+    ) -> impl Future<Output = Result<PluginEvent, SessionError>> + Send + 'static
+    where
+        F: FnMut(&PluginEvent) -> bool + Send + 'static,
+    {
+        let mut events = Box::pin(self.events());
+
+        async move {
+            while let Some(event) = events.next().await {
+                if predicate(&event) {
+                    return Ok(event);
+                }
+            }
+
+            Err(SessionError::Closed)
+        }
+    }
+
+    /// Opens a URL in the default browser and waits for a deep link
+    /// callback to the plugin, for browser based authorization flows
+    ///
+    /// A unique state token is generated and passed to `make_url` along with
+    /// a callback deep link for the plugin, the URL returned is opened and the
+    /// first deep link received with a `state` query parameter matching the
+    /// token is returned. The callback path can be changed using
+    /// [DeepLinkUrl::path], the `state` parameter must be preserved by the
+    /// service redirecting to the callback. The callback URL must be
+    /// percent-encoded when it is passed as a query parameter
+    ///
+    /// The matching deep link is still passed on to the deep link routes
+    /// and [Plugin::on_deep_link](crate::Plugin::on_deep_link)
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+    ///
+    /// # async fn example(session: tilepad_plugin_sdk::PluginSessionHandle) {
+    /// let ctx = session
+    ///     .await_deep_link(Duration::from_secs(300), |state, callback| {
+    ///         let redirect = callback.path("/auth/callback").query("state", state).build();
+    ///         let redirect = utf8_percent_encode(&redirect, NON_ALPHANUMERIC);
+    ///         format!("https://example.com/authorize?state={state}&redirect_uri={redirect}")
+    ///     })
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn await_deep_link<F>(
+        &self,
+        timeout: Duration,
+        make_url: F,
+    ) -> Result<DeepLinkContext, SessionError>
+    where
+        F: FnOnce(&str, DeepLinkUrl) -> String,
+    {
+        let state = Uuid::new_v4().simple().to_string();
+        let url = make_url(&state, self.deep_link_url()?);
+
+        let (tx, rx) = oneshot::channel();
+
+        self.subscriptions.add(Subscriber::new(
+            move |msg| match msg {
+                ServerPluginMessage::DeepLink { ctx } => {
+                    ctx.query.as_deref().is_some_and(|query| {
+                        parse_query(query)
+                            .iter()
+                            .any(|(key, value)| key == "state" && value == &state)
+                    })
+                }
+                _ => false,
+            },
+            tx,
+        ));
+
+        self.open_url(url)?;
+
+        let msg = tokio::time::timeout(timeout, rx)
+            .await
+            .map_err(|_| SessionError::Timeout)?
+            .map_err(|_| SessionError::Closed)?;
+
+        match msg {
+            ServerPluginMessage::DeepLink { ctx } => Ok(ctx),
+            _ => Err(SessionError::UnexpectedMessage),
+        }
+    }
+
     /// Tells tilepad to open the provided `url` in the
     /// default browser
     pub fn open_url(&self, url: String) -> Result<(), SessionError> {
@@ -318,8 +1226,11 @@ impl Stream for PluginSessionRx {
                 WsMessage::Ping(_) | WsMessage::Pong(_) | WsMessage::Frame(_) => continue,
 
                 // Expecting a text based protocol
-                WsMessage::Binary(_) => {
-                    return Poll::Ready(Some(Err(SessionError::UnexpectedMessage)));
+                WsMessage::Binary(bytes) => {
+                    let error = ProtocolError::BinaryMessage {
+                        raw: bytes.to_vec(),
+                    };
+                    return Poll::Ready(Some(Err(error.into())));
                 }
 
                 // Socket is closed
@@ -330,13 +1241,596 @@ impl Stream for PluginSessionRx {
 
             let msg: ServerPluginMessage = match serde_json::from_str(msg.as_str()) {
                 Ok(value) => value,
-                Err(cause) => {
-                    tracing::error!(?cause, "invalid or unknown message");
-                    continue;
-                }
+                Err(error) => match unknown_message(msg.as_str()) {
+                    Some(msg) => msg,
+                    None => {
+                        let error = ProtocolError::InvalidMessage {
+                            raw: msg.to_string(),
+                            error,
+                        };
+                        return Poll::Ready(Some(Err(error.into())));
+                    }
+                },
             };
 
             return Poll::Ready(Some(Ok(msg)));
         }
     }
 }
+
+/// Creates the error for a response that did not contain the expected
+/// message, errors from the host are mapped to [SessionError::Rejected]
+fn unexpected_response(msg: ServerPluginMessage) -> SessionError {
+    match msg {
+        ServerPluginMessage::Error { code, message, .. } => {
+            SessionError::Rejected { code, message }
+        }
+        _ => SessionError::UnexpectedMessage,
+    }
+}
+
+/// Creates an [ServerPluginMessage::Unknown] from a message that failed to
+/// deserialize, when the message has a type not known to the SDK
+fn unknown_message(msg: &str) -> Option<ServerPluginMessage> {
+    let message: serde_json::Value = serde_json::from_str(msg).ok()?;
+    let message_type = message.get("type")?.as_str()?;
+
+    if ServerPluginMessage::KNOWN_TYPES.contains(&message_type) {
+        return None;
+    }
+
+    tracing::debug!(message_type, "received unknown message type");
+
+    Some(ServerPluginMessage::Unknown {
+        message_type: message_type.to_string(),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{protocol::TileIconOptions, style::Color};
+
+    fn session() -> (PluginSessionHandle, WsRx) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let session = PluginSessionHandle::new(tx, Subscriptions::default());
+        session.register("plugin".to_string()).unwrap();
+        (session, rx)
+    }
+
+    fn click(session: &PluginSessionHandle, tile_id: TileId, plugin_id: &str) {
+        session.observe(&ServerPluginMessage::TileClicked {
+            ctx: TileInteractionContext {
+                device_id: Uuid::new_v4(),
+                plugin_id: plugin_id.to_string(),
+                action_id: "action".to_string(),
+                tile_id,
+            },
+            properties: serde_json::Value::Null,
+        });
+    }
+
+    /// Drains the sent messages, returning how many were sent
+    fn sent(rx: &mut WsRx) -> usize {
+        let mut count = 0;
+        while rx.try_recv().is_ok() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Drains the sent messages as JSON
+    fn messages(rx: &mut WsRx) -> Vec<serde_json::Value> {
+        let mut messages = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            if let WsMessage::Text(text) = message {
+                messages.push(serde_json::from_str(&text).unwrap());
+            }
+        }
+        messages
+    }
+
+    /// Completes registration with a host supporting `capabilities`
+    fn registered(session: &PluginSessionHandle, capabilities: &[&str]) {
+        session.observe(&ServerPluginMessage::Registered {
+            plugin_id: "plugin".to_string(),
+            host: HostInfo {
+                host_version: Some("1.0.0".to_string()),
+                protocol_version: Some(PROTOCOL_VERSION),
+                capabilities: capabilities.iter().map(ToString::to_string).collect(),
+            },
+        });
+    }
+
+    fn icon_theme() -> Theme {
+        Theme {
+            icon_padding: Some(4),
+            icon_background_color: Some(Color::BLACK),
+            icon_border_color: Some(Color::WHITE),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ownership_off_sends_all_mutations() {
+        let (session, mut rx) = session();
+        sent(&mut rx);
+
+        session
+            .set_tile_icon(Uuid::new_v4(), TileIcon::None)
+            .unwrap();
+        assert_eq!(sent(&mut rx), 1);
+    }
+
+    #[test]
+    fn ownership_warn_sends_unowned_mutations() {
+        let (session, mut rx) = session();
+        session.set_ownership_mode(OwnershipMode::Warn);
+        sent(&mut rx);
+
+        let other = Uuid::new_v4();
+        click(&session, other, "other");
+
+        session
+            .set_tile_icon(Uuid::new_v4(), TileIcon::None)
+            .unwrap();
+        session.set_tile_icon(other, TileIcon::None).unwrap();
+        assert_eq!(sent(&mut rx), 2);
+    }
+
+    #[test]
+    fn ownership_enforce_rejects_unowned_mutations() {
+        let (session, mut rx) = session();
+        session.set_ownership_mode(OwnershipMode::Enforce);
+        sent(&mut rx);
+
+        let (own, other, unknown) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        click(&session, own, "plugin");
+        click(&session, other, "other");
+
+        assert!(matches!(
+            session.set_tile_icon(unknown, TileIcon::None),
+            Err(SessionError::UnknownTile(tile_id)) if tile_id == unknown
+        ));
+        assert!(matches!(
+            session.set_tile_icon(other, TileIcon::None),
+            Err(SessionError::NotOwned { tile_id, plugin_id })
+                if tile_id == other && plugin_id == "other"
+        ));
+        assert_eq!(sent(&mut rx), 0);
+
+        session.set_tile_icon(own, TileIcon::None).unwrap();
+        assert_eq!(sent(&mut rx), 1);
+    }
+
+    #[test]
+    fn theme_icon_options_are_sent_with_icon_updates() {
+        let (session, mut rx) = session();
+        registered(&session, &[HostInfo::TILE_CONFIG]);
+        session.set_theme(icon_theme());
+        messages(&mut rx);
+
+        let tile_id = Uuid::new_v4();
+        session.set_tile_icon(tile_id, TileIcon::None).unwrap();
+
+        let message = messages(&mut rx).remove(0);
+        assert_eq!(message["type"], "SetTileConfig");
+        assert_eq!(message["icon"]["type"], "None");
+        assert_eq!(message["icon_options"]["padding"], 4);
+        assert_eq!(message["icon_options"]["border_color"], "#ffffff");
+    }
+
+    #[test]
+    fn theme_icon_options_fill_missing_config_options() {
+        let (session, mut rx) = session();
+        registered(&session, &[HostInfo::TILE_CONFIG]);
+        session.set_theme(icon_theme());
+        messages(&mut rx);
+
+        let tile_id = Uuid::new_v4();
+        let label = TileLabel::default();
+        session
+            .set_tile_config(tile_id, TileConfigUpdate::default().label(label))
+            .unwrap();
+        let message = messages(&mut rx).remove(0);
+        assert_eq!(message["icon_options"]["padding"], 4);
+
+        // Provided options are sent unchanged
+        let options = TileIconOptions {
+            padding: 8,
+            background_color: String::new(),
+            border_color: String::new(),
+        };
+        session
+            .set_tile_config(tile_id, TileConfigUpdate::default().icon_options(options))
+            .unwrap();
+        let message = messages(&mut rx).remove(0);
+        assert_eq!(message["icon_options"]["padding"], 8);
+        assert_eq!(message["icon_options"]["border_color"], "");
+    }
+
+    #[test]
+    fn icon_updates_without_theme_icon_styling_use_set_tile_icon() {
+        let (session, mut rx) = session();
+        registered(&session, &[HostInfo::TILE_CONFIG]);
+        session.set_theme(Theme {
+            icon_padding: Some(4),
+            ..Default::default()
+        });
+        messages(&mut rx);
+
+        session
+            .set_tile_icon(Uuid::new_v4(), TileIcon::None)
+            .unwrap();
+        assert_eq!(messages(&mut rx)[0]["type"], "SetTileIcon");
+    }
+
+    #[test]
+    fn icon_updates_without_tile_config_support_use_set_tile_icon() {
+        let (session, mut rx) = session();
+        registered(&session, &[]);
+        session.set_theme(icon_theme());
+        messages(&mut rx);
+
+        session
+            .set_tile_icon(Uuid::new_v4(), TileIcon::None)
+            .unwrap();
+        assert_eq!(messages(&mut rx)[0]["type"], "SetTileIcon");
+    }
+
+    #[test]
+    fn tile_properties_of_unknown_tiles_are_not_validated() {
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Properties {
+            threshold: u32,
+        }
+
+        let (session, mut rx) = session();
+        session.set_properties_schemas(PropertiesSchemas::new().action::<Properties>("action"));
+        sent(&mut rx);
+
+        let unknown = Uuid::new_v4();
+        session
+            .set_tile_properties(unknown, serde_json::json!({}))
+            .unwrap();
+        assert_eq!(sent(&mut rx), 1);
+
+        let known = Uuid::new_v4();
+        click(&session, known, "plugin");
+        assert!(matches!(
+            session.set_tile_properties(known, serde_json::json!({})),
+            Err(SessionError::Schema(_))
+        ));
+        assert_eq!(sent(&mut rx), 0);
+    }
+
+    /// Responds to the request `request_id` with an error from the host
+    fn reject(session: &PluginSessionHandle, request_id: u64) {
+        let msg = ServerPluginMessage::Error {
+            request_id: Some(request_id),
+            code: "invalid".to_string(),
+            message: "invalid request".to_string(),
+        };
+        session.observe(&msg);
+        session.subscriptions.apply(&msg);
+    }
+
+    #[test]
+    fn with_ack_requires_capability() {
+        let (session, mut rx) = session();
+        registered(&session, &[]);
+        sent(&mut rx);
+
+        let result =
+            session.with_ack(|session| session.set_tile_icon(Uuid::new_v4(), TileIcon::None));
+        assert!(matches!(
+            result,
+            Err(SessionError::Unsupported { capability }) if capability == HostInfo::ACKNOWLEDGEMENTS
+        ));
+        assert_eq!(sent(&mut rx), 0);
+    }
+
+    #[test]
+    fn with_ack_tags_requests() {
+        let (session, mut rx) = session();
+        registered(&session, &[HostInfo::ACKNOWLEDGEMENTS]);
+        sent(&mut rx);
+
+        let _ack = session
+            .with_ack(|session| {
+                session.set_tile_icon(Uuid::new_v4(), TileIcon::None)?;
+                session.set_tile_icon(Uuid::new_v4(), TileIcon::None)
+            })
+            .unwrap();
+        session
+            .set_tile_icon(Uuid::new_v4(), TileIcon::None)
+            .unwrap();
+
+        let messages = messages(&mut rx);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0]["request_id"], 1);
+        assert_eq!(messages[1]["request_id"], 2);
+        assert!(messages[2].get("request_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn with_ack_resolves_once_acknowledged() {
+        let (session, mut rx) = session();
+        registered(&session, &[HostInfo::ACKNOWLEDGEMENTS]);
+        sent(&mut rx);
+
+        let mut ack = session
+            .with_ack(|session| {
+                session.set_tile_icon(Uuid::new_v4(), TileIcon::None)?;
+                session.set_tile_icon(Uuid::new_v4(), TileIcon::None)
+            })
+            .unwrap();
+
+        session.observe(&ServerPluginMessage::Ack { request_id: 2 });
+        assert!(futures_util::poll!(&mut ack).is_pending());
+
+        session.observe(&ServerPluginMessage::Ack { request_id: 1 });
+        assert!(matches!(ack.await, Ok(())));
+    }
+
+    #[tokio::test]
+    async fn with_ack_resolves_to_rejection() {
+        let (session, mut rx) = session();
+        registered(&session, &[HostInfo::ACKNOWLEDGEMENTS]);
+        sent(&mut rx);
+
+        let ack = session
+            .with_ack(|session| session.set_tile_icon(Uuid::new_v4(), TileIcon::None))
+            .unwrap();
+        reject(&session, 1);
+
+        assert!(matches!(
+            ack.await,
+            Err(SessionError::Rejected { code, message })
+                if code == "invalid" && message == "invalid request"
+        ));
+    }
+
+    #[tokio::test]
+    async fn with_ack_fails_when_session_closes() {
+        let (session, mut rx) = session();
+        registered(&session, &[HostInfo::ACKNOWLEDGEMENTS]);
+        sent(&mut rx);
+
+        let ack = session
+            .with_ack(|session| session.set_tile_icon(Uuid::new_v4(), TileIcon::None))
+            .unwrap();
+        session.clear_observed();
+
+        assert!(matches!(ack.await, Err(SessionError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn responses_resolve_to_rejection_for_tagged_requests() {
+        let (session, mut rx) = session();
+        registered(&session, &[HostInfo::ACKNOWLEDGEMENTS]);
+        sent(&mut rx);
+
+        let properties = tokio::spawn({
+            let session = session.clone();
+            async move { session.get_properties().await }
+        });
+        tokio::task::yield_now().await;
+
+        let messages = messages(&mut rx);
+        assert_eq!(messages[0]["type"], "GetProperties");
+        let request_id = messages[0]["request_id"].as_u64().unwrap();
+
+        reject(&session, request_id + 1);
+        reject(&session, request_id);
+
+        assert!(matches!(
+            properties.await.unwrap(),
+            Err(SessionError::Rejected { code, .. }) if code == "invalid"
+        ));
+    }
+
+    #[tokio::test]
+    async fn responses_are_untagged_without_acknowledgements() {
+        let (session, mut rx) = session();
+        registered(&session, &[]);
+        sent(&mut rx);
+
+        let properties = tokio::spawn({
+            let session = session.clone();
+            async move { session.get_properties().await }
+        });
+        tokio::task::yield_now().await;
+
+        let messages = messages(&mut rx);
+        assert!(messages[0].get("request_id").is_none());
+
+        session
+            .subscriptions
+            .apply(&ServerPluginMessage::Properties {
+                properties: serde_json::json!({ "a": 1 }),
+            });
+        assert_eq!(
+            properties.await.unwrap().unwrap(),
+            serde_json::json!({ "a": 1 })
+        );
+    }
+
+    fn inspector_ctx() -> InspectorContext {
+        InspectorContext {
+            profile_id: Uuid::new_v4(),
+            folder_id: Uuid::new_v4(),
+            plugin_id: "plugin".to_string(),
+            action_id: "action".to_string(),
+            tile_id: Uuid::new_v4(),
+        }
+    }
+
+    /// Starts a call to `method` on the inspector at `ctx`, returning
+    /// the call and the ID of the request sent to the inspector
+    async fn start_call(
+        session: &PluginSessionHandle,
+        rx: &mut WsRx,
+        ctx: &InspectorContext,
+        timeout: Duration,
+    ) -> (
+        tokio::task::JoinHandle<Result<Vec<String>, SessionError>>,
+        u64,
+    ) {
+        let call = tokio::spawn({
+            let session = session.clone();
+            let ctx = ctx.clone();
+            async move {
+                session
+                    .call_inspector_timeout(ctx, "getDevices", (), timeout)
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+
+        let messages = messages(rx);
+        assert_eq!(messages[0]["type"], "SendToInspector");
+        let request = &messages[0]["message"];
+        assert_eq!(request["rpc"], "Request");
+        assert_eq!(request["method"], "getDevices");
+        (call, request["id"].as_u64().unwrap())
+    }
+
+    #[tokio::test]
+    async fn call_inspector_resolves_response() {
+        let (session, mut rx) = session();
+        sent(&mut rx);
+
+        let ctx = inspector_ctx();
+        let (call, id) = start_call(&session, &mut rx, &ctx, RPC_CALL_TIMEOUT).await;
+        assert!(session.has_rpc_call(&ctx, id));
+
+        session.resolve_rpc_call(&ctx, id, Ok(serde_json::json!(["Device 1"])));
+        assert_eq!(call.await.unwrap().unwrap(), ["Device 1"]);
+    }
+
+    #[tokio::test]
+    async fn call_inspector_resolves_error() {
+        let (session, mut rx) = session();
+        sent(&mut rx);
+
+        let ctx = inspector_ctx();
+        let (call, id) = start_call(&session, &mut rx, &ctx, RPC_CALL_TIMEOUT).await;
+
+        session.resolve_rpc_call(&ctx, id, Err(RpcError::internal("failed")));
+        assert!(matches!(
+            call.await.unwrap(),
+            Err(SessionError::Rpc(error)) if error.code == RpcError::INTERNAL
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn call_inspector_times_out() {
+        let (session, mut rx) = session();
+        sent(&mut rx);
+
+        let ctx = inspector_ctx();
+        let (call, id) = start_call(&session, &mut rx, &ctx, RPC_CALL_TIMEOUT).await;
+
+        tokio::time::sleep(RPC_CALL_TIMEOUT).await;
+        assert!(matches!(call.await.unwrap(), Err(SessionError::Timeout)));
+        assert!(!session.has_rpc_call(&ctx, id));
+    }
+
+    #[tokio::test]
+    async fn call_inspector_fails_when_inspector_closes() {
+        let (session, mut rx) = session();
+        sent(&mut rx);
+
+        let ctx = inspector_ctx();
+        session.inspector_opened(ctx.clone());
+        let (call, id) = start_call(&session, &mut rx, &ctx, RPC_CALL_TIMEOUT).await;
+
+        session.inspector_closed(&ctx);
+        assert!(matches!(call.await.unwrap(), Err(SessionError::Closed)));
+        assert!(!session.has_rpc_call(&ctx, id));
+    }
+
+    #[tokio::test]
+    async fn events_stream_published_messages() {
+        let (session, _rx) = session();
+        let mut events = session.events();
+        let mut clicks = session.tile_clicks("action");
+
+        let tile_id = Uuid::new_v4();
+        session.publish_event(&ServerPluginMessage::Ack { request_id: 1 });
+        session.publish_event(&ServerPluginMessage::TileClicked {
+            ctx: TileInteractionContext {
+                device_id: Uuid::new_v4(),
+                plugin_id: "plugin".to_string(),
+                action_id: "action".to_string(),
+                tile_id,
+            },
+            properties: serde_json::Value::Null,
+        });
+
+        assert!(matches!(
+            events.next().await,
+            Some(PluginEvent::Ack { request_id: 1 })
+        ));
+        assert!(matches!(
+            events.next().await,
+            Some(PluginEvent::TileClicked { .. })
+        ));
+        assert_eq!(clicks.next().await.unwrap().0.tile_id, tile_id);
+
+        session.clear_observed();
+        assert!(events.next().await.is_none());
+        assert!(session.events().next().await.is_none());
+    }
+
+    #[test]
+    fn tile_config_requires_capability() {
+        let (session, mut rx) = session();
+        sent(&mut rx);
+
+        // Capabilities are unknown until registered
+        let config = TileConfigUpdate::default().icon(TileIcon::None);
+        assert!(matches!(
+            session.set_tile_config(Uuid::new_v4(), config.clone()),
+            Err(SessionError::Unsupported { capability }) if capability == HostInfo::TILE_CONFIG
+        ));
+
+        registered(&session, &[HostInfo::ACKNOWLEDGEMENTS]);
+        assert!(matches!(
+            session.set_tile_config(Uuid::new_v4(), config.clone()),
+            Err(SessionError::Unsupported { .. })
+        ));
+        assert_eq!(sent(&mut rx), 0);
+
+        registered(&session, &[HostInfo::TILE_CONFIG]);
+        session.set_tile_config(Uuid::new_v4(), config).unwrap();
+        assert_eq!(messages(&mut rx)[0]["type"], "SetTileConfig");
+    }
+
+    #[test]
+    fn registered_host_details_are_parsed() {
+        let msg: ServerPluginMessage = serde_json::from_value(serde_json::json!({
+            "type": "Registered",
+            "plugin_id": "plugin",
+            "host_version": "0.9.0",
+            "capabilities": [HostInfo::ACKNOWLEDGEMENTS]
+        }))
+        .unwrap();
+
+        let (session, _rx) = session();
+        assert_eq!(session.host_info(), None);
+
+        session.observe(&msg);
+        let host = session.host_info().unwrap();
+        assert_eq!(host.host_version.as_deref(), Some("0.9.0"));
+        assert_eq!(host.protocol_version, None);
+        assert!(session.supports(HostInfo::ACKNOWLEDGEMENTS));
+        assert!(!session.supports(HostInfo::TILE_CONFIG));
+    }
+}
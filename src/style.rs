@@ -0,0 +1,883 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::protocol::{IconPackId, LabelAlign, PluginId, TileIcon, TileIconOptions, TileLabel};
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum StyleError {
+    /// Color was not a valid hex, rgb(a), hsl(a) or named color
+    #[error("invalid color \"{0}\"")]
+    InvalidColor(String),
+
+    /// Font name was empty or contained invalid characters
+    #[error("invalid font \"{0}\"")]
+    InvalidFont(String),
+
+    /// Font size was outside of [FontSize::MIN] to [FontSize::MAX]
+    #[error("font size {0} is out of range")]
+    FontSizeOutOfRange(u32),
+
+    /// Icon padding was larger than [TileIconOptions::MAX_PADDING]
+    #[error("icon padding {0} is out of range")]
+    PaddingOutOfRange(u32),
+
+    /// Icon path or URL was empty or malformed
+    #[error("invalid icon \"{0}\"")]
+    InvalidIcon(String),
+}
+
+/// Color used for tile labels and icons
+///
+/// Can be parsed from hex (`#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`),
+/// `rgb(r, g, b)`, `rgba(r, g, b, a)`, `hsl(h, s%, l%)`, `hsla(h, s%, l%, a)`
+/// or a CSS named color. Colors are always written in the hex form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+/// Named colors that can be used in place of a hex color, the
+/// CSS Color Module Level 4 named colors
+const NAMED_COLORS: &[(&str, Color)] = &[
+    ("transparent", Color::rgba(0, 0, 0, 0)),
+    ("aliceblue", Color::rgb(240, 248, 255)),
+    ("antiquewhite", Color::rgb(250, 235, 215)),
+    ("aqua", Color::rgb(0, 255, 255)),
+    ("aquamarine", Color::rgb(127, 255, 212)),
+    ("azure", Color::rgb(240, 255, 255)),
+    ("beige", Color::rgb(245, 245, 220)),
+    ("bisque", Color::rgb(255, 228, 196)),
+    ("black", Color::rgb(0, 0, 0)),
+    ("blanchedalmond", Color::rgb(255, 235, 205)),
+    ("blue", Color::rgb(0, 0, 255)),
+    ("blueviolet", Color::rgb(138, 43, 226)),
+    ("brown", Color::rgb(165, 42, 42)),
+    ("burlywood", Color::rgb(222, 184, 135)),
+    ("cadetblue", Color::rgb(95, 158, 160)),
+    ("chartreuse", Color::rgb(127, 255, 0)),
+    ("chocolate", Color::rgb(210, 105, 30)),
+    ("coral", Color::rgb(255, 127, 80)),
+    ("cornflowerblue", Color::rgb(100, 149, 237)),
+    ("cornsilk", Color::rgb(255, 248, 220)),
+    ("crimson", Color::rgb(220, 20, 60)),
+    ("cyan", Color::rgb(0, 255, 255)),
+    ("darkblue", Color::rgb(0, 0, 139)),
+    ("darkcyan", Color::rgb(0, 139, 139)),
+    ("darkgoldenrod", Color::rgb(184, 134, 11)),
+    ("darkgray", Color::rgb(169, 169, 169)),
+    ("darkgreen", Color::rgb(0, 100, 0)),
+    ("darkgrey", Color::rgb(169, 169, 169)),
+    ("darkkhaki", Color::rgb(189, 183, 107)),
+    ("darkmagenta", Color::rgb(139, 0, 139)),
+    ("darkolivegreen", Color::rgb(85, 107, 47)),
+    ("darkorange", Color::rgb(255, 140, 0)),
+    ("darkorchid", Color::rgb(153, 50, 204)),
+    ("darkred", Color::rgb(139, 0, 0)),
+    ("darksalmon", Color::rgb(233, 150, 122)),
+    ("darkseagreen", Color::rgb(143, 188, 143)),
+    ("darkslateblue", Color::rgb(72, 61, 139)),
+    ("darkslategray", Color::rgb(47, 79, 79)),
+    ("darkslategrey", Color::rgb(47, 79, 79)),
+    ("darkturquoise", Color::rgb(0, 206, 209)),
+    ("darkviolet", Color::rgb(148, 0, 211)),
+    ("deeppink", Color::rgb(255, 20, 147)),
+    ("deepskyblue", Color::rgb(0, 191, 255)),
+    ("dimgray", Color::rgb(105, 105, 105)),
+    ("dimgrey", Color::rgb(105, 105, 105)),
+    ("dodgerblue", Color::rgb(30, 144, 255)),
+    ("firebrick", Color::rgb(178, 34, 34)),
+    ("floralwhite", Color::rgb(255, 250, 240)),
+    ("forestgreen", Color::rgb(34, 139, 34)),
+    ("fuchsia", Color::rgb(255, 0, 255)),
+    ("gainsboro", Color::rgb(220, 220, 220)),
+    ("ghostwhite", Color::rgb(248, 248, 255)),
+    ("gold", Color::rgb(255, 215, 0)),
+    ("goldenrod", Color::rgb(218, 165, 32)),
+    ("gray", Color::rgb(128, 128, 128)),
+    ("green", Color::rgb(0, 128, 0)),
+    ("greenyellow", Color::rgb(173, 255, 47)),
+    ("grey", Color::rgb(128, 128, 128)),
+    ("honeydew", Color::rgb(240, 255, 240)),
+    ("hotpink", Color::rgb(255, 105, 180)),
+    ("indianred", Color::rgb(205, 92, 92)),
+    ("indigo", Color::rgb(75, 0, 130)),
+    ("ivory", Color::rgb(255, 255, 240)),
+    ("khaki", Color::rgb(240, 230, 140)),
+    ("lavender", Color::rgb(230, 230, 250)),
+    ("lavenderblush", Color::rgb(255, 240, 245)),
+    ("lawngreen", Color::rgb(124, 252, 0)),
+    ("lemonchiffon", Color::rgb(255, 250, 205)),
+    ("lightblue", Color::rgb(173, 216, 230)),
+    ("lightcoral", Color::rgb(240, 128, 128)),
+    ("lightcyan", Color::rgb(224, 255, 255)),
+    ("lightgoldenrodyellow", Color::rgb(250, 250, 210)),
+    ("lightgray", Color::rgb(211, 211, 211)),
+    ("lightgreen", Color::rgb(144, 238, 144)),
+    ("lightgrey", Color::rgb(211, 211, 211)),
+    ("lightpink", Color::rgb(255, 182, 193)),
+    ("lightsalmon", Color::rgb(255, 160, 122)),
+    ("lightseagreen", Color::rgb(32, 178, 170)),
+    ("lightskyblue", Color::rgb(135, 206, 250)),
+    ("lightslategray", Color::rgb(119, 136, 153)),
+    ("lightslategrey", Color::rgb(119, 136, 153)),
+    ("lightsteelblue", Color::rgb(176, 196, 222)),
+    ("lightyellow", Color::rgb(255, 255, 224)),
+    ("lime", Color::rgb(0, 255, 0)),
+    ("limegreen", Color::rgb(50, 205, 50)),
+    ("linen", Color::rgb(250, 240, 230)),
+    ("magenta", Color::rgb(255, 0, 255)),
+    ("maroon", Color::rgb(128, 0, 0)),
+    ("mediumaquamarine", Color::rgb(102, 205, 170)),
+    ("mediumblue", Color::rgb(0, 0, 205)),
+    ("mediumorchid", Color::rgb(186, 85, 211)),
+    ("mediumpurple", Color::rgb(147, 112, 219)),
+    ("mediumseagreen", Color::rgb(60, 179, 113)),
+    ("mediumslateblue", Color::rgb(123, 104, 238)),
+    ("mediumspringgreen", Color::rgb(0, 250, 154)),
+    ("mediumturquoise", Color::rgb(72, 209, 204)),
+    ("mediumvioletred", Color::rgb(199, 21, 133)),
+    ("midnightblue", Color::rgb(25, 25, 112)),
+    ("mintcream", Color::rgb(245, 255, 250)),
+    ("mistyrose", Color::rgb(255, 228, 225)),
+    ("moccasin", Color::rgb(255, 228, 181)),
+    ("navajowhite", Color::rgb(255, 222, 173)),
+    ("navy", Color::rgb(0, 0, 128)),
+    ("oldlace", Color::rgb(253, 245, 230)),
+    ("olive", Color::rgb(128, 128, 0)),
+    ("olivedrab", Color::rgb(107, 142, 35)),
+    ("orange", Color::rgb(255, 165, 0)),
+    ("orangered", Color::rgb(255, 69, 0)),
+    ("orchid", Color::rgb(218, 112, 214)),
+    ("palegoldenrod", Color::rgb(238, 232, 170)),
+    ("palegreen", Color::rgb(152, 251, 152)),
+    ("paleturquoise", Color::rgb(175, 238, 238)),
+    ("palevioletred", Color::rgb(219, 112, 147)),
+    ("papayawhip", Color::rgb(255, 239, 213)),
+    ("peachpuff", Color::rgb(255, 218, 185)),
+    ("peru", Color::rgb(205, 133, 63)),
+    ("pink", Color::rgb(255, 192, 203)),
+    ("plum", Color::rgb(221, 160, 221)),
+    ("powderblue", Color::rgb(176, 224, 230)),
+    ("purple", Color::rgb(128, 0, 128)),
+    ("rebeccapurple", Color::rgb(102, 51, 153)),
+    ("red", Color::rgb(255, 0, 0)),
+    ("rosybrown", Color::rgb(188, 143, 143)),
+    ("royalblue", Color::rgb(65, 105, 225)),
+    ("saddlebrown", Color::rgb(139, 69, 19)),
+    ("salmon", Color::rgb(250, 128, 114)),
+    ("sandybrown", Color::rgb(244, 164, 96)),
+    ("seagreen", Color::rgb(46, 139, 87)),
+    ("seashell", Color::rgb(255, 245, 238)),
+    ("sienna", Color::rgb(160, 82, 45)),
+    ("silver", Color::rgb(192, 192, 192)),
+    ("skyblue", Color::rgb(135, 206, 235)),
+    ("slateblue", Color::rgb(106, 90, 205)),
+    ("slategray", Color::rgb(112, 128, 144)),
+    ("slategrey", Color::rgb(112, 128, 144)),
+    ("snow", Color::rgb(255, 250, 250)),
+    ("springgreen", Color::rgb(0, 255, 127)),
+    ("steelblue", Color::rgb(70, 130, 180)),
+    ("tan", Color::rgb(210, 180, 140)),
+    ("teal", Color::rgb(0, 128, 128)),
+    ("thistle", Color::rgb(216, 191, 216)),
+    ("tomato", Color::rgb(255, 99, 71)),
+    ("turquoise", Color::rgb(64, 224, 208)),
+    ("violet", Color::rgb(238, 130, 238)),
+    ("wheat", Color::rgb(245, 222, 179)),
+    ("white", Color::rgb(255, 255, 255)),
+    ("whitesmoke", Color::rgb(245, 245, 245)),
+    ("yellow", Color::rgb(255, 255, 0)),
+    ("yellowgreen", Color::rgb(154, 205, 50)),
+];
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    /// Creates a fully opaque color
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Self::rgba(red, green, blue, 255)
+    }
+
+    /// Creates a color with an alpha channel
+    pub const fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    /// Parses a hex color without the leading `#`
+    fn parse_hex(value: &str) -> Option<Self> {
+        if !value.chars().all(|char| char.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        // Expand the short forms (rgb / rgba) into their long forms
+        let value: String = match value.len() {
+            3 | 4 => value.chars().flat_map(|char| [char, char]).collect(),
+            6 | 8 => value.to_string(),
+            _ => return None,
+        };
+
+        let channel = |index: usize| u8::from_str_radix(&value[index..index + 2], 16).ok();
+        let alpha = if value.len() == 8 { channel(6)? } else { 255 };
+
+        Some(Self::rgba(channel(0)?, channel(2)?, channel(4)?, alpha))
+    }
+
+    /// Parses the arguments of a `rgb(...)` or `rgba(...)` function
+    fn parse_rgb(args: &str, has_alpha: bool) -> Option<Self> {
+        let parts = Self::split_args(args, has_alpha)?;
+        let channel = |value: &str| value.parse::<u8>().ok();
+        let alpha = if has_alpha {
+            Self::parse_alpha(parts[3])?
+        } else {
+            255
+        };
+
+        Some(Self::rgba(
+            channel(parts[0])?,
+            channel(parts[1])?,
+            channel(parts[2])?,
+            alpha,
+        ))
+    }
+
+    /// Parses the arguments of a `hsl(...)` or `hsla(...)` function
+    fn parse_hsl(args: &str, has_alpha: bool) -> Option<Self> {
+        let parts = Self::split_args(args, has_alpha)?;
+        let percent = |value: &str| {
+            let value = value.strip_suffix('%')?.parse::<f32>().ok()? / 100.0;
+            (0.0..=1.0).contains(&value).then_some(value)
+        };
+
+        let hue = parts[0]
+            .strip_suffix("deg")
+            .unwrap_or(parts[0])
+            .parse::<f32>()
+            .ok()?
+            .rem_euclid(360.0);
+        let saturation = percent(parts[1])?;
+        let lightness = percent(parts[2])?;
+        let alpha = if has_alpha {
+            Self::parse_alpha(parts[3])?
+        } else {
+            255
+        };
+
+        // Conversion from https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+        let channel = |n: f32| {
+            let k = (n + hue / 30.0) % 12.0;
+            let a = saturation * lightness.min(1.0 - lightness);
+            let value = lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0);
+            (value * 255.0).round() as u8
+        };
+
+        Some(Self::rgba(channel(0.0), channel(8.0), channel(4.0), alpha))
+    }
+
+    /// Splits the comma separated arguments of a color function
+    fn split_args(args: &str, has_alpha: bool) -> Option<Vec<&str>> {
+        let parts: Vec<&str> = args.split(',').map(str::trim).collect();
+        let expected = if has_alpha { 4 } else { 3 };
+        (parts.len() == expected).then_some(parts)
+    }
+
+    /// Parses an alpha value as either a number from 0 to 1 or a percentage
+    fn parse_alpha(value: &str) -> Option<u8> {
+        let alpha = match value.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+            None => value.parse::<f32>().ok()?,
+        };
+
+        if !(0.0..=1.0).contains(&alpha) {
+            return None;
+        }
+
+        Some((alpha * 255.0).round() as u8)
+    }
+}
+
+impl FromStr for Color {
+    type Err = StyleError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let trimmed = value.trim();
+        let lower = trimmed.to_ascii_lowercase();
+
+        let color = if let Some(hex) = lower.strip_prefix('#') {
+            Self::parse_hex(hex)
+        } else if let Some(args) = function_args(&lower, "rgba") {
+            Self::parse_rgb(args, true)
+        } else if let Some(args) = function_args(&lower, "rgb") {
+            Self::parse_rgb(args, false)
+        } else if let Some(args) = function_args(&lower, "hsla") {
+            Self::parse_hsl(args, true)
+        } else if let Some(args) = function_args(&lower, "hsl") {
+            Self::parse_hsl(args, false)
+        } else {
+            NAMED_COLORS
+                .iter()
+                .find(|(name, _)| lower.eq(name))
+                .map(|(_, color)| *color)
+        };
+
+        color.ok_or_else(|| StyleError::InvalidColor(value.to_string()))
+    }
+}
+
+/// Arguments of a color function call such as `rgb(...)`
+fn function_args<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    value
+        .strip_prefix(name)?
+        .strip_prefix('(')?
+        .strip_suffix(')')
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)?;
+        if self.alpha != 255 {
+            write!(f, "{:02x}", self.alpha)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Name of the font family used for a label
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Font(String);
+
+impl Font {
+    /// Maximum length of a font name
+    pub const MAX_LENGTH: usize = 64;
+
+    /// Creates a new font, validating the font name
+    pub fn new(name: impl Into<String>) -> Result<Self, StyleError> {
+        let name: String = name.into();
+        let trimmed = name.trim();
+
+        let is_valid = !trimmed.is_empty()
+            && trimmed.len() <= Self::MAX_LENGTH
+            && !trimmed
+                .chars()
+                .any(|char| char.is_control() || matches!(char, ';' | '{' | '}' | '"'));
+
+        if !is_valid {
+            return Err(StyleError::InvalidFont(name));
+        }
+
+        Ok(Self(trimmed.to_string()))
+    }
+
+    /// Name of the font
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Font {
+    type Err = StyleError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::new(value)
+    }
+}
+
+impl fmt::Display for Font {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Font {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Font {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        Self::new(value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Size of a label font
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct FontSize(u32);
+
+impl FontSize {
+    pub const MIN: u32 = 4;
+    pub const MAX: u32 = 128;
+
+    /// Creates a new font size, checking that the size is within
+    /// the [FontSize::MIN] and [FontSize::MAX] range
+    pub fn new(size: u32) -> Result<Self, StyleError> {
+        if !(Self::MIN..=Self::MAX).contains(&size) {
+            return Err(StyleError::FontSizeOutOfRange(size));
+        }
+
+        Ok(Self(size))
+    }
+
+    /// Size as a number
+    pub fn get(&self) -> u32 {
+        self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for FontSize {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = u32::deserialize(deserializer)?;
+        Self::new(value).map_err(serde::de::Error::custom)
+    }
+}
+
+impl TileLabel {
+    /// Creates a builder for a validated label
+    pub fn builder() -> TileLabelBuilder {
+        TileLabelBuilder::default()
+    }
+
+    /// Checks that the colors, font and font size of the
+    /// label are valid, empty colors are left for the host
+    /// to treat as unset
+    pub fn validate(&self) -> Result<(), StyleError> {
+        validate_color(self.color.as_deref())?;
+        validate_color(self.outline_color.as_deref())?;
+
+        if let Some(font) = &self.font {
+            Font::new(font.as_str())?;
+        }
+
+        if let Some(font_size) = self.font_size {
+            FontSize::new(font_size)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TileIconOptions {
+    /// Creates a builder for validated icon options
+    pub fn builder() -> TileIconOptionsBuilder {
+        TileIconOptionsBuilder::default()
+    }
+
+    /// Maximum padding around a tile icon
+    pub const MAX_PADDING: u32 = 100;
+
+    /// Checks that the padding and colors of the options are valid,
+    /// empty colors are left for the host to treat as unset
+    pub fn validate(&self) -> Result<(), StyleError> {
+        if self.padding > Self::MAX_PADDING {
+            return Err(StyleError::PaddingOutOfRange(self.padding));
+        }
+
+        validate_color(Some(&self.background_color))?;
+        validate_color(Some(&self.border_color))?;
+        Ok(())
+    }
+}
+
+impl TileIcon {
+    /// Creates a builder for a validated icon
+    pub fn builder() -> TileIconBuilder {
+        TileIconBuilder::default()
+    }
+
+    /// Checks that the paths and URL of the icon are not empty
+    /// and that URLs use a http(s) or data scheme
+    pub fn validate(&self) -> Result<(), StyleError> {
+        let path = match self {
+            TileIcon::None | TileIcon::Unknown(_) => return Ok(()),
+            TileIcon::PluginIcon { icon: path, .. }
+            | TileIcon::IconPack { path, .. }
+            | TileIcon::Uploaded { path }
+            | TileIcon::Display { path } => path,
+            TileIcon::Url { src } => {
+                let is_valid = ["http://", "https://", "data:"]
+                    .iter()
+                    .any(|scheme| src.len() > scheme.len() && src.starts_with(scheme));
+
+                if !is_valid {
+                    return Err(StyleError::InvalidIcon(src.clone()));
+                }
+
+                return Ok(());
+            }
+        };
+
+        if path.trim().is_empty() {
+            return Err(StyleError::InvalidIcon(path.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that a color is empty or valid
+fn validate_color(color: Option<&str>) -> Result<(), StyleError> {
+    match color {
+        Some(color) if !color.trim().is_empty() => Color::from_str(color).map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+/// Builder for a [TileLabel] that validates the label
+/// styling when built
+///
+/// Colors, fonts and font sizes take the typed [Color], [Font] and
+/// [FontSize] which are validated when they are created
+#[derive(Debug, Default)]
+pub struct TileLabelBuilder {
+    label: TileLabel,
+}
+
+impl TileLabelBuilder {
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.label.enabled = Some(enabled);
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label.label = Some(label.into());
+        self
+    }
+
+    pub fn align(mut self, align: LabelAlign) -> Self {
+        self.label.align = Some(align);
+        self
+    }
+
+    pub fn font(mut self, font: Font) -> Self {
+        self.label.font = Some(font.to_string());
+        self
+    }
+
+    pub fn font_size(mut self, font_size: FontSize) -> Self {
+        self.label.font_size = Some(font_size.get());
+        self
+    }
+
+    pub fn bold(mut self, bold: bool) -> Self {
+        self.label.bold = Some(bold);
+        self
+    }
+
+    pub fn italic(mut self, italic: bool) -> Self {
+        self.label.italic = Some(italic);
+        self
+    }
+
+    pub fn underline(mut self, underline: bool) -> Self {
+        self.label.underline = Some(underline);
+        self
+    }
+
+    pub fn outline(mut self, outline: bool) -> Self {
+        self.label.outline = Some(outline);
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.label.color = Some(color.to_string());
+        self
+    }
+
+    pub fn outline_color(mut self, outline_color: Color) -> Self {
+        self.label.outline_color = Some(outline_color.to_string());
+        self
+    }
+
+    /// Validates and creates the label
+    pub fn build(self) -> Result<TileLabel, StyleError> {
+        self.label.validate()?;
+        Ok(self.label)
+    }
+}
+
+/// Builder for a [TileIcon] that validates the icon when built
+#[derive(Debug, Default)]
+pub struct TileIconBuilder {
+    icon: TileIcon,
+}
+
+impl TileIconBuilder {
+    pub fn plugin_icon(mut self, plugin_id: PluginId, icon: impl Into<String>) -> Self {
+        self.icon = TileIcon::PluginIcon {
+            plugin_id,
+            icon: icon.into(),
+        };
+        self
+    }
+
+    pub fn icon_pack(mut self, pack_id: IconPackId, path: impl Into<String>) -> Self {
+        self.icon = TileIcon::IconPack {
+            pack_id,
+            path: path.into(),
+        };
+        self
+    }
+
+    pub fn url(mut self, src: impl Into<String>) -> Self {
+        self.icon = TileIcon::Url { src: src.into() };
+        self
+    }
+
+    pub fn uploaded(mut self, path: impl Into<String>) -> Self {
+        self.icon = TileIcon::Uploaded { path: path.into() };
+        self
+    }
+
+    pub fn display(mut self, path: impl Into<String>) -> Self {
+        self.icon = TileIcon::Display { path: path.into() };
+        self
+    }
+
+    /// Validates and creates the icon
+    pub fn build(self) -> Result<TileIcon, StyleError> {
+        self.icon.validate()?;
+        Ok(self.icon)
+    }
+}
+
+/// Builder for [TileIconOptions] that validates the padding
+/// when built
+#[derive(Debug)]
+pub struct TileIconOptionsBuilder {
+    options: TileIconOptions,
+}
+
+impl Default for TileIconOptionsBuilder {
+    fn default() -> Self {
+        Self {
+            options: TileIconOptions {
+                padding: 0,
+                background_color: Color::TRANSPARENT.to_string(),
+                border_color: Color::TRANSPARENT.to_string(),
+            },
+        }
+    }
+}
+
+impl TileIconOptionsBuilder {
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.options.padding = padding;
+        self
+    }
+
+    pub fn background_color(mut self, background_color: Color) -> Self {
+        self.options.background_color = background_color.to_string();
+        self
+    }
+
+    pub fn border_color(mut self, border_color: Color) -> Self {
+        self.options.border_color = border_color.to_string();
+        self
+    }
+
+    /// Validates and creates the icon options
+    pub fn build(self) -> Result<TileIconOptions, StyleError> {
+        self.options.validate()?;
+        Ok(self.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!("#f00".parse(), Ok(Color::rgb(255, 0, 0)));
+        assert_eq!("#f008".parse(), Ok(Color::rgba(255, 0, 0, 0x88)));
+        assert_eq!("#00FF7f".parse(), Ok(Color::rgb(0, 255, 127)));
+        assert_eq!("#00ff7f80".parse(), Ok(Color::rgba(0, 255, 127, 0x80)));
+        assert_eq!(" #ffffff ".parse(), Ok(Color::WHITE));
+    }
+
+    #[test]
+    fn rejects_invalid_hex_colors() {
+        for value in ["#", "#ff", "#fffff", "#ggg", "#fffffffff", "fff"] {
+            assert!(value.parse::<Color>().is_err(), "{value} should be invalid");
+        }
+    }
+
+    #[test]
+    fn parses_rgb_colors() {
+        assert_eq!("rgb(1, 2, 3)".parse(), Ok(Color::rgb(1, 2, 3)));
+        assert_eq!("RGBA(1,2,3,0.5)".parse(), Ok(Color::rgba(1, 2, 3, 128)));
+        assert_eq!("rgba(1, 2, 3, 50%)".parse(), Ok(Color::rgba(1, 2, 3, 128)));
+
+        for value in [
+            "rgb(1, 2)",
+            "rgb(256, 0, 0)",
+            "rgba(1, 2, 3, 2)",
+            "rgb(1, 2, 3",
+        ] {
+            assert!(value.parse::<Color>().is_err(), "{value} should be invalid");
+        }
+    }
+
+    #[test]
+    fn parses_hsl_colors() {
+        assert_eq!("hsl(0, 100%, 50%)".parse(), Ok(Color::rgb(255, 0, 0)));
+        assert_eq!("hsl(120deg, 100%, 25%)".parse(), Ok(Color::rgb(0, 128, 0)));
+        assert_eq!("hsl(240, 100%, 50%)".parse(), Ok(Color::rgb(0, 0, 255)));
+        assert_eq!("hsl(0, 0%, 100%)".parse(), Ok(Color::WHITE));
+        assert_eq!(
+            "hsla(0, 100%, 50%, 0.5)".parse(),
+            Ok(Color::rgba(255, 0, 0, 128))
+        );
+
+        for value in [
+            "hsl(0, 100, 50%)",
+            "hsl(0, 150%, 50%)",
+            "hsla(0, 100%, 50%)",
+        ] {
+            assert!(value.parse::<Color>().is_err(), "{value} should be invalid");
+        }
+    }
+
+    #[test]
+    fn parses_named_colors() {
+        assert_eq!("transparent".parse(), Ok(Color::TRANSPARENT));
+        assert_eq!("Red".parse(), Ok(Color::rgb(255, 0, 0)));
+        assert_eq!("rebeccapurple".parse(), Ok(Color::rgb(102, 51, 153)));
+        assert!("notacolor".parse::<Color>().is_err());
+        assert!("".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn displays_as_hex() {
+        assert_eq!(Color::rgb(255, 0, 127).to_string(), "#ff007f");
+        assert_eq!(Color::rgba(0, 0, 0, 0).to_string(), "#00000000");
+
+        let color: Color = "hsl(200, 50%, 40%)".parse().unwrap();
+        assert_eq!(color.to_string().parse(), Ok(color));
+    }
+
+    #[test]
+    fn label_validation_allows_empty_colors() {
+        let label = TileLabel {
+            color: Some(String::new()),
+            outline_color: Some("hsl(0, 0%, 0%)".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(label.validate(), Ok(()));
+
+        let label = TileLabel {
+            color: Some("reed".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            label.validate(),
+            Err(StyleError::InvalidColor("reed".to_string()))
+        );
+    }
+
+    #[test]
+    fn icon_builder_validates_icon() {
+        assert!(
+            TileIcon::builder()
+                .url("https://example.com/a.png")
+                .build()
+                .is_ok()
+        );
+        assert!(
+            TileIcon::builder()
+                .url("ftp://example.com")
+                .build()
+                .is_err()
+        );
+        assert!(TileIcon::builder().uploaded(" ").build().is_err());
+        assert!(TileIcon::builder().build().is_ok());
+    }
+
+    #[test]
+    fn font_size_is_range_checked() {
+        assert_eq!(FontSize::new(FontSize::MIN).map(|size| size.get()), Ok(4));
+        assert_eq!(FontSize::new(FontSize::MAX).map(|size| size.get()), Ok(128));
+        assert_eq!(FontSize::new(3), Err(StyleError::FontSizeOutOfRange(3)));
+        assert_eq!(FontSize::new(129), Err(StyleError::FontSizeOutOfRange(129)));
+        assert!(serde_json::from_str::<FontSize>("0").is_err());
+
+        let label = TileLabel {
+            font_size: Some(200),
+            ..Default::default()
+        };
+        assert_eq!(label.validate(), Err(StyleError::FontSizeOutOfRange(200)));
+    }
+
+    #[test]
+    fn icon_padding_is_range_checked() {
+        let options = TileIconOptions::builder()
+            .padding(TileIconOptions::MAX_PADDING)
+            .background_color(Color::BLACK)
+            .build()
+            .unwrap();
+        assert_eq!(options.background_color, "#000000");
+
+        assert_eq!(
+            TileIconOptions::builder().padding(101).build().unwrap_err(),
+            StyleError::PaddingOutOfRange(101)
+        );
+    }
+
+    #[test]
+    fn label_builder_uses_typed_values() {
+        let label = TileLabel::builder()
+            .label("CPU")
+            .font(Font::new(" Roboto ").unwrap())
+            .font_size(FontSize::new(14).unwrap())
+            .color("red".parse().unwrap())
+            .outline_color(Color::BLACK)
+            .build()
+            .unwrap();
+
+        assert_eq!(label.font.as_deref(), Some("Roboto"));
+        assert_eq!(label.font_size, Some(14));
+        assert_eq!(label.color.as_deref(), Some("#ff0000"));
+        assert_eq!(label.outline_color.as_deref(), Some("#000000"));
+    }
+}
@@ -17,6 +17,11 @@ impl Subscriptions {
 
     pub fn apply(&self, msg: &ServerPluginMessage) {
         self.subscribers.lock().retain_mut(|subscriber| {
+            // Drop subscribers that are no longer waiting
+            if subscriber.tx.as_ref().is_none_or(|tx| tx.is_closed()) {
+                return false;
+            }
+
             if (subscriber.filter)(msg) {
                 if let Some(tx) = subscriber.tx.take() {
                     _ = tx.send(msg.clone());
@@ -0,0 +1,408 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::protocol::{TileLabel, TilePosition};
+
+/// Character used when text is truncated
+const ELLIPSIS: &str = "\u{2026}";
+
+/// Advance widths for the printable ASCII characters (space to `~`) in
+/// 1/1000 of an em, taken from the Helvetica metrics in the Adobe Core 14
+/// AFM files
+#[rustfmt::skip]
+const ASCII_WIDTHS: [u16; 95] = [
+    // space ! " # $ % & ' ( ) * + , - . /
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    // 0 - 9
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556,
+    // : ; < = > ? @
+    278, 278, 584, 584, 584, 556, 1015,
+    // A - Z
+    667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611,
+    // [ \ ] ^ _ `
+    278, 278, 278, 469, 556, 333,
+    // a - z
+    556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833,
+    556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500,
+    // { | } ~
+    334, 260, 334, 584,
+];
+
+/// Width of narrow characters not covered by the ASCII metrics in 1/1000
+/// of an em, the Helvetica width of the digits and most lowercase letters
+const DEFAULT_WIDTH: u16 = 556;
+
+/// Width of wide characters (CJK, emoji) in 1/1000 of an em, wide characters
+/// are drawn on a square em box
+const WIDE_WIDTH: u16 = 1000;
+
+/// Multiplier applied to widths when the text is bold, an approximation
+/// of the wider Helvetica-Bold metrics rather than per character widths
+const BOLD_FACTOR: f32 = 1.06;
+
+/// Estimated widths of common font families relative to Helvetica
+const DEFAULT_FONT_WIDTHS: [(&str, f32); 8] = [
+    ("Helvetica", 1.0),
+    ("Arial", 1.0),
+    ("Roboto", 1.0),
+    ("Verdana", 1.15),
+    ("Tahoma", 1.02),
+    ("Georgia", 1.05),
+    ("Times New Roman", 0.9),
+    ("Courier New", 1.1),
+];
+
+/// Layout settings used to fit label text within a tile
+///
+/// Sizes are in the same units as the label font size, a tile
+/// spanning a single row and column is `cell_size` units square
+///
+/// The defaults are estimates rather than sizes reported by Tilepad, the
+/// cell is 100 units so the 6 unit padding is 6% of a tile, with a 12 unit
+/// label font that can shrink down to 8 units. Set them from the actual
+/// tile and label sizes when they are known
+///
+/// Text is measured using Helvetica metrics scaled by the width of the label
+/// font from [LabelLayout::font_widths], fonts that are not listed are measured
+/// as Helvetica so labels using a wider font may still overflow. Characters outside of
+/// ASCII are estimated from their Unicode width, each grapheme cluster
+/// (such as an emoji sequence joined with ZWJ) is measured as a single
+/// narrow or wide character
+#[derive(Debug, Clone)]
+pub struct LabelLayout {
+    /// Size of a single grid cell
+    pub cell_size: f32,
+    /// Padding between the tile edge and the label text
+    pub padding: f32,
+    /// Line height as a multiple of the font size
+    pub line_height: f32,
+    /// Maximum number of lines the text can wrap onto
+    pub max_lines: usize,
+    /// Font size used when the label does not specify one
+    pub default_font_size: u32,
+    /// Smallest font size the text can be shrunk to, shrinking
+    /// is disabled when this is the same as the label font size
+    pub min_font_size: u32,
+    /// Average width of font families relative to Helvetica, font
+    /// names are matched ignoring case
+    pub font_widths: Vec<(String, f32)>,
+}
+
+impl Default for LabelLayout {
+    fn default() -> Self {
+        Self {
+            cell_size: 100.0,
+            padding: 6.0,
+            line_height: 1.2,
+            max_lines: 3,
+            default_font_size: 12,
+            min_font_size: 8,
+            font_widths: DEFAULT_FONT_WIDTHS
+                .iter()
+                .map(|(font, width)| (font.to_string(), *width))
+                .collect(),
+        }
+    }
+}
+
+/// Label text after it has been fit within a tile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FittedLabel {
+    /// Lines of text
+    pub lines: Vec<String>,
+    /// Font size the text was fit at
+    pub font_size: u32,
+    /// Whether the text was truncated with an ellipsis
+    pub truncated: bool,
+}
+
+impl FittedLabel {
+    /// Text of the label with the lines joined by line breaks
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Applies the fitted text and font size to `label`
+    pub fn apply_to_label(&self, label: &mut TileLabel) {
+        label.label = Some(self.text());
+        label.font_size = Some(self.font_size);
+    }
+}
+
+impl LabelLayout {
+    /// Fits `text` within a tile at `position` using the font, font
+    /// size and weight from `label`
+    ///
+    /// The text is wrapped onto multiple lines, then shrunk down to
+    /// [LabelLayout::min_font_size] and finally truncated with an
+    /// ellipsis if it still does not fit
+    pub fn fit(&self, text: &str, position: &TilePosition, label: &TileLabel) -> FittedLabel {
+        let bold = label.bold.unwrap_or_default();
+        let font_width = self.font_width(label.font.as_deref());
+        let width = self.cell_size * position.column_span.max(1) as f32 - self.padding * 2.0;
+        let height = self.cell_size * position.row_span.max(1) as f32 - self.padding * 2.0;
+
+        let start_size = label.font_size.unwrap_or(self.default_font_size).max(1);
+        let min_size = self.min_font_size.clamp(1, start_size);
+
+        for font_size in (min_size..=start_size).rev() {
+            let lines = wrap(text, font_size as f32 * font_width, bold, width);
+            if lines.len() <= self.line_limit(font_size, height) {
+                return FittedLabel {
+                    lines,
+                    font_size,
+                    truncated: false,
+                };
+            }
+        }
+
+        // Text does not fit at the smallest size, truncate the overflowing lines
+        let scaled_size = min_size as f32 * font_width;
+        let mut lines = wrap(text, scaled_size, bold, width);
+        let limit = self.line_limit(min_size, height);
+        lines.truncate(limit);
+
+        if let Some(last) = lines.last_mut() {
+            *last = ellipsize(last, scaled_size, bold, width);
+        }
+
+        FittedLabel {
+            lines,
+            font_size: min_size,
+            truncated: true,
+        }
+    }
+
+    /// Width of the `font` family relative to Helvetica, fonts that
+    /// are not listed in [LabelLayout::font_widths] are measured as Helvetica
+    pub fn font_width(&self, font: Option<&str>) -> f32 {
+        let Some(font) = font else {
+            return 1.0;
+        };
+
+        self.font_widths
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(font.trim()))
+            .map_or(1.0, |(_, width)| *width)
+    }
+
+    /// Number of lines that fit within `height` at `font_size`
+    fn line_limit(&self, font_size: u32, height: f32) -> usize {
+        let by_height = (height / (font_size as f32 * self.line_height)).floor() as usize;
+        by_height.clamp(1, self.max_lines.max(1))
+    }
+}
+
+/// Measures the width of `text` at `font_size`, see [LabelLayout]
+/// for the limitations of the measurements
+pub fn measure_text(text: &str, font_size: f32, bold: bool) -> f32 {
+    let units: u32 = text
+        .graphemes(true)
+        .map(grapheme_width)
+        .map(u32::from)
+        .sum();
+    let factor = if bold { BOLD_FACTOR } else { 1.0 };
+    units as f32 / 1000.0 * font_size * factor
+}
+
+/// Width of a single grapheme cluster in 1/1000 of an em
+fn grapheme_width(grapheme: &str) -> u16 {
+    let mut chars = grapheme.chars();
+    if let (Some(char), None) = (chars.next(), chars.next())
+        && (' '..='~').contains(&char)
+    {
+        return ASCII_WIDTHS[char as usize - ' ' as usize];
+    }
+
+    match grapheme.width() {
+        0 => 0,
+        1 => DEFAULT_WIDTH,
+        _ => WIDE_WIDTH,
+    }
+}
+
+/// Wraps `text` into lines that fit within `max_width`, words that
+/// are too long for a line are broken between grapheme clusters
+fn wrap(text: &str, font_size: f32, bold: bool, max_width: f32) -> Vec<String> {
+    let fits = |value: &str| measure_text(value, font_size, bold) <= max_width;
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+
+        for word in paragraph.split_word_bounds() {
+            let is_space = word.trim().is_empty();
+            if is_space && line.is_empty() {
+                continue;
+            }
+
+            if fits(format!("{line}{word}").trim_end()) {
+                line.push_str(word);
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(line.trim_end().to_string());
+                line.clear();
+            }
+
+            if is_space {
+                continue;
+            }
+
+            // Break words that are too long to fit on a line
+            for grapheme in word.graphemes(true) {
+                if !line.is_empty() && !fits(&format!("{line}{grapheme}")) {
+                    lines.push(std::mem::take(&mut line));
+                }
+
+                line.push_str(grapheme);
+            }
+        }
+
+        lines.push(line.trim_end().to_string());
+    }
+
+    lines
+}
+
+/// Appends an ellipsis to `line` removing grapheme clusters
+/// until it fits within `max_width`
+fn ellipsize(line: &str, font_size: f32, bold: bool, max_width: f32) -> String {
+    let mut graphemes: Vec<&str> = line.graphemes(true).collect();
+
+    loop {
+        let text = format!("{}{ELLIPSIS}", graphemes.concat().trim_end());
+        if graphemes.is_empty() || measure_text(&text, font_size, bold) <= max_width {
+            return text;
+        }
+
+        graphemes.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(column_span: u32, row_span: u32) -> TilePosition {
+        TilePosition {
+            row: 0,
+            column: 0,
+            row_span,
+            column_span,
+        }
+    }
+
+    fn fit(text: &str, font_size: u32) -> FittedLabel {
+        let label = TileLabel {
+            font_size: Some(font_size),
+            ..Default::default()
+        };
+        LabelLayout::default().fit(text, &position(1, 1), &label)
+    }
+
+    #[test]
+    fn measures_ascii_with_metrics() {
+        assert_eq!(measure_text("i", 1000.0, false), 222.0);
+        assert_eq!(measure_text("WW", 1000.0, false), 1888.0);
+        assert!(measure_text("WW", 1000.0, true) > 1888.0);
+    }
+
+    #[test]
+    fn measures_graphemes_as_single_characters() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(measure_text(family, 1000.0, false), 1000.0);
+        assert_eq!(measure_text("e\u{301}", 1000.0, false), 556.0);
+        assert_eq!(measure_text("\u{4E2D}", 1000.0, false), 1000.0);
+    }
+
+    #[test]
+    fn keeps_text_that_fits() {
+        let fitted = fit("CPU", 12);
+        assert_eq!(fitted.lines, ["CPU"]);
+        assert_eq!(fitted.font_size, 12);
+        assert!(!fitted.truncated);
+    }
+
+    #[test]
+    fn wraps_onto_multiple_lines() {
+        let fitted = fit("Processor usage percent", 12);
+        assert_eq!(fitted.lines, ["Processor", "usage percent"]);
+        assert_eq!(fitted.font_size, 12);
+        assert!(!fitted.truncated);
+    }
+
+    #[test]
+    fn breaks_long_words() {
+        let fitted = fit("Supercalifragilistic", 12);
+        assert_eq!(fitted.lines, ["Supercalifragilis", "tic"]);
+        assert!(!fitted.truncated);
+    }
+
+    #[test]
+    fn shrinks_when_wrapping_is_not_enough() {
+        let text = "one two three four five six seven eight nine ten eleven twelve";
+        let fitted = fit(text, 12);
+        assert!(fitted.font_size < 12);
+        assert!(fitted.font_size >= LabelLayout::default().min_font_size);
+        assert!(!fitted.truncated);
+        assert_eq!(fitted.lines.join(" "), text);
+    }
+
+    #[test]
+    fn truncates_with_ellipsis() {
+        let text = "word ".repeat(40);
+        let fitted = fit(&text, 12);
+        let layout = LabelLayout::default();
+        assert!(fitted.truncated);
+        assert_eq!(fitted.font_size, layout.min_font_size);
+        assert_eq!(fitted.lines.len(), layout.max_lines);
+        assert!(fitted.lines.last().unwrap().ends_with(ELLIPSIS));
+
+        let width = layout.cell_size - layout.padding * 2.0;
+        for line in &fitted.lines {
+            assert!(measure_text(line, fitted.font_size as f32, false) <= width);
+        }
+    }
+
+    #[test]
+    fn wider_fonts_wrap_sooner() {
+        let layout = LabelLayout::default();
+        let text = "Network usage";
+        let label = |font: &str| TileLabel {
+            font: Some(font.to_string()),
+            font_size: Some(12),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            layout.fit(text, &position(1, 1), &label("Arial")).lines,
+            [text]
+        );
+        assert_eq!(
+            layout.fit(text, &position(1, 1), &label("verdana")).lines,
+            ["Network", "usage"]
+        );
+    }
+
+    #[test]
+    fn font_widths_can_be_set() {
+        let mut layout = LabelLayout::default();
+        assert_eq!(layout.font_width(None), 1.0);
+        assert_eq!(layout.font_width(Some("Unknown Sans")), 1.0);
+        assert_eq!(layout.font_width(Some(" times new roman ")), 0.9);
+
+        layout.font_widths.push(("Unknown Sans".to_string(), 1.2));
+        assert_eq!(layout.font_width(Some("Unknown Sans")), 1.2);
+    }
+
+    #[test]
+    fn larger_tiles_fit_more_text() {
+        let label = TileLabel::default();
+        let layout = LabelLayout::default();
+        let text = "Processor usage percent";
+        assert_eq!(layout.fit(text, &position(2, 1), &label).lines, [text]);
+    }
+}
@@ -0,0 +1,438 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    protocol::{LabelAlign, ServerPluginMessage, TileIconOptions, TileId, TileLabel},
+    style::{Color, Font, FontSize},
+};
+
+/// Visual styling shared across the tiles of a plugin
+///
+/// Fields that are not specified by a label are filled in from
+/// the theme when the label is sent, fields specified by the label
+/// always take priority over the theme
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Theme {
+    /// Label font
+    pub font: Option<Font>,
+    /// Label font size
+    pub font_size: Option<FontSize>,
+    /// Label text color
+    pub color: Option<Color>,
+    /// Label outline color
+    pub outline_color: Option<Color>,
+    /// Label alignment
+    pub align: Option<LabelAlign>,
+
+    /// Padding around the tile icon
+    pub icon_padding: Option<u32>,
+    /// Background color behind the tile icon
+    pub icon_background_color: Option<Color>,
+    /// Border color around the tile icon
+    pub icon_border_color: Option<Color>,
+}
+
+impl Theme {
+    /// Creates a theme where fields missing from `self` are
+    /// taken from `base`
+    pub fn merge(&self, base: &Theme) -> Theme {
+        Theme {
+            font: self.font.clone().or_else(|| base.font.clone()),
+            font_size: self.font_size.or(base.font_size),
+            color: self.color.or(base.color),
+            outline_color: self.outline_color.or(base.outline_color),
+            align: self.align.clone().or_else(|| base.align.clone()),
+            icon_padding: self.icon_padding.or(base.icon_padding),
+            icon_background_color: self.icon_background_color.or(base.icon_background_color),
+            icon_border_color: self.icon_border_color.or(base.icon_border_color),
+        }
+    }
+
+    /// Fills in the fields of `label` that are not already
+    /// specified using the theme
+    pub fn apply_to_label(&self, label: &mut TileLabel) {
+        if label.font.is_none() {
+            label.font = self.font.as_ref().map(Font::to_string);
+        }
+
+        if label.font_size.is_none() {
+            label.font_size = self.font_size.map(|size| size.get());
+        }
+
+        if label.color.is_none() {
+            label.color = self.color.map(|color| color.to_string());
+        }
+
+        if label.outline_color.is_none() {
+            label.outline_color = self.outline_color.map(|color| color.to_string());
+        }
+
+        if label.align.is_none() {
+            label.align = self.align.clone();
+        }
+    }
+
+    /// Removes the fields of `label` that have the same value as the theme,
+    /// used to recover the un-themed label from a label the theme was
+    /// previously applied to
+    pub fn strip_from_label(&self, label: &mut TileLabel) {
+        if label.font.is_some() && label.font == self.font.as_ref().map(Font::to_string) {
+            label.font = None;
+        }
+
+        if label.font_size.is_some() && label.font_size == self.font_size.map(|size| size.get()) {
+            label.font_size = None;
+        }
+
+        if label.color.is_some() && label.color == self.color.map(|color| color.to_string()) {
+            label.color = None;
+        }
+
+        if label.outline_color.is_some()
+            && label.outline_color == self.outline_color.map(|color| color.to_string())
+        {
+            label.outline_color = None;
+        }
+
+        if label.align.is_some() && label.align == self.align {
+            label.align = None;
+        }
+    }
+
+    /// Icon options with the icon styling from the theme applied over
+    /// `base`, fields not specified by the theme are kept from `base`
+    ///
+    /// [None] when the theme does not specify any icon styling, or when
+    /// there are no `base` options and the theme does not specify all of
+    /// the icon fields
+    pub fn icon_options(&self, base: Option<&TileIconOptions>) -> Option<TileIconOptions> {
+        if self.icon_padding.is_none()
+            && self.icon_background_color.is_none()
+            && self.icon_border_color.is_none()
+        {
+            return None;
+        }
+
+        Some(TileIconOptions {
+            padding: self
+                .icon_padding
+                .or_else(|| base.map(|base| base.padding))?,
+            background_color: self
+                .icon_background_color
+                .map(|color| color.to_string())
+                .or_else(|| base.map(|base| base.background_color.clone()))?,
+            border_color: self
+                .icon_border_color
+                .map(|color| color.to_string())
+                .or_else(|| base.map(|base| base.border_color.clone()))?,
+        })
+    }
+}
+
+/// Store for the active plugin theme, per tile overrides and the
+/// labels sent before the theme was applied
+#[derive(Default, Clone)]
+pub(crate) struct Themes {
+    inner: Arc<Mutex<ThemesInner>>,
+}
+
+#[derive(Default)]
+struct ThemesInner {
+    /// Theme applied to all tiles
+    theme: Theme,
+    /// Theme applied to all tiles before the current theme
+    previous: Theme,
+    /// Theme overrides for specific tiles
+    overrides: HashMap<TileId, Theme>,
+    /// Labels for tiles before the theme was applied, used
+    /// to re-apply the theme when it changes
+    labels: HashMap<TileId, TileLabel>,
+}
+
+impl Themes {
+    pub fn theme(&self) -> Theme {
+        self.inner.lock().theme.clone()
+    }
+
+    pub fn set_theme(&self, theme: Theme) {
+        let inner = &mut *self.inner.lock();
+        inner.previous = std::mem::replace(&mut inner.theme, theme);
+    }
+
+    pub fn set_override(&self, tile_id: TileId, theme: Theme) {
+        self.inner.lock().overrides.insert(tile_id, theme);
+    }
+
+    pub fn remove_override(&self, tile_id: &TileId) {
+        self.inner.lock().overrides.remove(tile_id);
+    }
+
+    /// Theme for a specific tile, the tile override merged
+    /// with the plugin theme
+    pub fn resolve(&self, tile_id: &TileId) -> Theme {
+        let inner = &*self.inner.lock();
+        match inner.overrides.get(tile_id) {
+            Some(theme) => theme.merge(&inner.theme),
+            None => inner.theme.clone(),
+        }
+    }
+
+    /// Label last sent for `tile_id` before the theme was applied
+    pub fn label(&self, tile_id: &TileId) -> Option<TileLabel> {
+        self.inner.lock().labels.get(tile_id).cloned()
+    }
+
+    /// Label for `tile_id` before the theme was applied, falls back to the
+    /// `current` label of the tile with the fields owned by the current or
+    /// previous theme removed
+    pub fn base_label(&self, tile_id: &TileId, current: TileLabel) -> TileLabel {
+        if let Some(label) = self.label(tile_id) {
+            return label;
+        }
+
+        let inner = &*self.inner.lock();
+        let override_theme = inner.overrides.get(tile_id);
+
+        let mut label = current;
+        for theme in [&inner.theme, &inner.previous] {
+            match override_theme {
+                Some(override_theme) => override_theme.merge(theme).strip_from_label(&mut label),
+                None => theme.strip_from_label(&mut label),
+            }
+        }
+
+        label
+    }
+
+    /// Drops the labels stored for tiles that are no longer visible
+    pub fn apply(&self, msg: &ServerPluginMessage) {
+        if let ServerPluginMessage::VisibleTiles { tiles } = msg {
+            self.inner
+                .lock()
+                .labels
+                .retain(|tile_id, _| tiles.iter().any(|tile| &tile.id == tile_id));
+        }
+    }
+
+    /// Drops the tile overrides and stored labels when the session ends,
+    /// the tiles can't be tracked once the session is closed
+    pub fn clear(&self) {
+        let inner = &mut *self.inner.lock();
+        inner.overrides.clear();
+        inner.labels.clear();
+    }
+
+    /// Applies the theme for `tile_id` to the provided `label`
+    /// storing the original label
+    pub fn apply_label(&self, tile_id: TileId, label: TileLabel) -> TileLabel {
+        let theme = self.resolve(&tile_id);
+
+        let mut themed = label.clone();
+        theme.apply_to_label(&mut themed);
+
+        self.inner.lock().labels.insert(tile_id, label);
+
+        themed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::protocol::{TileConfig, TileIcon, TileModel, TilePosition};
+
+    fn red() -> Color {
+        Color::rgb(255, 0, 0)
+    }
+
+    fn theme() -> Theme {
+        Theme {
+            font: Some(Font::new("Roboto").unwrap()),
+            font_size: Some(FontSize::new(12).unwrap()),
+            color: Some(Color::WHITE),
+            ..Default::default()
+        }
+    }
+
+    fn tile(id: TileId) -> TileModel {
+        TileModel {
+            id,
+            config: TileConfig {
+                icon: TileIcon::None,
+                label: TileLabel::default(),
+                icon_options: None,
+            },
+            properties: Default::default(),
+            folder_id: Uuid::new_v4(),
+            plugin_id: "plugin".to_string(),
+            action_id: "action".to_string(),
+            position: TilePosition {
+                row: 0,
+                column: 0,
+                row_span: 1,
+                column_span: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn merge_prefers_own_fields() {
+        let theme = Theme {
+            color: Some(red()),
+            icon_padding: Some(2),
+            ..Default::default()
+        };
+        let merged = theme.merge(&self::theme());
+
+        assert_eq!(merged.color, Some(red()));
+        assert_eq!(merged.font, self::theme().font);
+        assert_eq!(merged.font_size, self::theme().font_size);
+        assert_eq!(merged.icon_padding, Some(2));
+        assert_eq!(merged.icon_border_color, None);
+    }
+
+    #[test]
+    fn apply_keeps_label_fields() {
+        let mut label = TileLabel {
+            color: Some("#000000".to_string()),
+            ..Default::default()
+        };
+        theme().apply_to_label(&mut label);
+
+        assert_eq!(label.color.as_deref(), Some("#000000"));
+        assert_eq!(label.font.as_deref(), Some("Roboto"));
+        assert_eq!(label.font_size, Some(12));
+    }
+
+    #[test]
+    fn strip_removes_only_theme_values() {
+        let mut label = TileLabel {
+            label: Some("CPU".to_string()),
+            font: Some("Roboto".to_string()),
+            font_size: Some(14),
+            color: Some("#ffffff".to_string()),
+            ..Default::default()
+        };
+        theme().strip_from_label(&mut label);
+
+        assert_eq!(label.label.as_deref(), Some("CPU"));
+        assert_eq!(label.font, None);
+        assert_eq!(label.font_size, Some(14));
+        assert_eq!(label.color, None);
+    }
+
+    #[test]
+    fn icon_options_merge_over_base() {
+        let base = TileIconOptions {
+            padding: 1,
+            background_color: "#000000".to_string(),
+            border_color: "#000000".to_string(),
+        };
+        let theme = Theme {
+            icon_border_color: Some(red()),
+            ..Default::default()
+        };
+
+        let options = theme.icon_options(Some(&base)).unwrap();
+        assert_eq!(options.padding, 1);
+        assert_eq!(options.background_color, "#000000");
+        assert_eq!(options.border_color, "#ff0000");
+
+        // Partial theme styling can't be sent without base options
+        assert!(theme.icon_options(None).is_none());
+        assert!(Theme::default().icon_options(Some(&base)).is_none());
+    }
+
+    #[test]
+    fn overrides_apply_to_their_tile() {
+        let themes = Themes::default();
+        themes.set_theme(theme());
+
+        let (tile_id, other_id) = (Uuid::new_v4(), Uuid::new_v4());
+        themes.set_override(
+            tile_id,
+            Theme {
+                color: Some(red()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(themes.resolve(&tile_id).color, Some(red()));
+        assert_eq!(themes.resolve(&tile_id).font, theme().font);
+        assert_eq!(themes.resolve(&other_id).color, Some(Color::WHITE));
+
+        let label = themes.apply_label(tile_id, TileLabel::default());
+        assert_eq!(label.color.as_deref(), Some("#ff0000"));
+
+        themes.remove_override(&tile_id);
+        assert_eq!(themes.resolve(&tile_id).color, Some(Color::WHITE));
+    }
+
+    #[test]
+    fn base_label_uses_stored_label() {
+        let themes = Themes::default();
+        themes.set_theme(theme());
+
+        let tile_id = Uuid::new_v4();
+        let label = TileLabel {
+            label: Some("CPU".to_string()),
+            ..Default::default()
+        };
+        let themed = themes.apply_label(tile_id, label);
+        assert_eq!(themed.font.as_deref(), Some("Roboto"));
+
+        let base = themes.base_label(&tile_id, themed);
+        assert_eq!(base.label.as_deref(), Some("CPU"));
+        assert_eq!(base.font, None);
+    }
+
+    #[test]
+    fn base_label_strips_current_and_previous_theme() {
+        let themes = Themes::default();
+        themes.set_theme(theme());
+        themes.set_theme(Theme {
+            color: Some(red()),
+            ..Default::default()
+        });
+
+        let tile_id = Uuid::new_v4();
+        let current = TileLabel {
+            font: Some("Roboto".to_string()),
+            color: Some("#ff0000".to_string()),
+            outline_color: Some("#000000".to_string()),
+            ..Default::default()
+        };
+
+        let base = themes.base_label(&tile_id, current);
+        assert_eq!(base.font, None);
+        assert_eq!(base.color, None);
+        assert_eq!(base.outline_color.as_deref(), Some("#000000"));
+    }
+
+    #[test]
+    fn hidden_tiles_drop_stored_labels_and_keep_overrides() {
+        let themes = Themes::default();
+        let (visible_id, hidden_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        themes.apply_label(visible_id, TileLabel::default());
+        themes.apply_label(hidden_id, TileLabel::default());
+        themes.set_override(hidden_id, theme());
+
+        themes.apply(&ServerPluginMessage::VisibleTiles {
+            tiles: vec![tile(visible_id)],
+        });
+
+        assert!(themes.label(&visible_id).is_some());
+        assert!(themes.label(&hidden_id).is_none());
+        assert_eq!(themes.resolve(&hidden_id), theme());
+
+        themes.clear();
+        assert!(themes.label(&visible_id).is_none());
+        assert_eq!(themes.resolve(&hidden_id), Theme::default());
+    }
+}
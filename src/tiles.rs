@@ -0,0 +1,280 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
+
+use crate::protocol::{
+    ActionId, DeviceId, DisplayContext, PluginId, ServerPluginMessage, TileIcon, TileId, TileModel,
+};
+
+/// How tile mutations are checked against the tiles known to the plugin
+/// before they are sent, see [PluginSessionHandle::set_ownership_mode](crate::PluginSessionHandle::set_ownership_mode)
+///
+/// Tiles become known from the tiles received for devices, visible tiles,
+/// clicks and inspector or display messages. Tiles are forgotten when they
+/// are removed from a device or are no longer visible
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OwnershipMode {
+    /// Tiles are not checked
+    #[default]
+    Off,
+    /// Mutations of unknown tiles or tiles owned by another plugin
+    /// are logged as a warning but are still sent
+    Warn,
+    /// Mutations of unknown tiles or tiles owned by another plugin
+    /// are rejected with an error before sending
+    Enforce,
+}
+
+/// Plugin and action a known tile is using
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TileAction {
+    pub plugin_id: PluginId,
+    pub action_id: ActionId,
+}
+
+/// Store for the tiles the plugin has learned about from the
+/// messages received from the server
+#[derive(Default, Clone)]
+pub(crate) struct KnownTiles {
+    inner: Arc<Mutex<KnownTilesInner>>,
+}
+
+#[derive(Default)]
+struct KnownTilesInner {
+    /// Plugin and action for each known tile
+    tiles: HashMap<TileId, TileAction>,
+    /// Latest snapshot of the tiles visible on each device
+    devices: HashMap<DeviceId, Vec<TileModel>>,
+}
+
+impl KnownTiles {
+    /// Records the tiles referenced by a message from the server
+    pub fn apply(&self, msg: &ServerPluginMessage) {
+        match msg {
+            ServerPluginMessage::TileClicked { ctx, .. } => {
+                self.insert(ctx.tile_id, &ctx.plugin_id, &ctx.action_id);
+            }
+            ServerPluginMessage::InspectorOpen { ctx }
+            | ServerPluginMessage::RecvFromInspector { ctx, .. } => {
+                self.insert(ctx.tile_id, &ctx.plugin_id, &ctx.action_id);
+            }
+            ServerPluginMessage::RecvFromDisplay { ctx, .. } => {
+                self.insert(ctx.tile_id, &ctx.plugin_id, &ctx.action_id);
+            }
+            ServerPluginMessage::DeviceTiles { device_id, tiles } => {
+                self.replace_device(*device_id, tiles);
+                self.extend(tiles);
+            }
+            ServerPluginMessage::VisibleTiles { tiles } => {
+                self.retain_visible(tiles);
+                self.extend(tiles);
+            }
+            _ => {}
+        }
+    }
+
+    /// Records the plugin and action used by a tile
+    pub fn insert(&self, tile_id: TileId, plugin_id: &str, action_id: &str) {
+        self.inner.lock().tiles.insert(
+            tile_id,
+            TileAction {
+                plugin_id: plugin_id.to_string(),
+                action_id: action_id.to_string(),
+            },
+        );
+    }
+
+    /// Records the plugin and action used by each of the `tiles`
+    pub fn extend(&self, tiles: &[TileModel]) {
+        let known = &mut self.inner.lock().tiles;
+        for tile in tiles {
+            known.insert(
+                tile.id,
+                TileAction {
+                    plugin_id: tile.plugin_id.clone(),
+                    action_id: tile.action_id.clone(),
+                },
+            );
+        }
+    }
+
+    /// Replaces the snapshot of the tiles on `device_id`, forgetting the
+    /// tiles that were removed from the device and are not on another device
+    fn replace_device(&self, device_id: DeviceId, tiles: &[TileModel]) {
+        let inner = &mut *self.inner.lock();
+        let Some(previous) = inner.devices.insert(device_id, tiles.to_vec()) else {
+            return;
+        };
+
+        for tile in previous {
+            let is_shown = inner
+                .devices
+                .values()
+                .flatten()
+                .any(|other| other.id == tile.id);
+
+            if !is_shown {
+                inner.tiles.remove(&tile.id);
+            }
+        }
+    }
+
+    /// Forgets the tiles that are no longer visible, tiles from the
+    /// device snapshots are kept
+    fn retain_visible(&self, visible: &[TileModel]) {
+        let inner = &mut *self.inner.lock();
+        let devices = &inner.devices;
+
+        inner.tiles.retain(|tile_id, _| {
+            visible.iter().any(|tile| tile.id.eq(tile_id))
+                || devices.values().flatten().any(|tile| tile.id.eq(tile_id))
+        });
+    }
+
+    pub fn get(&self, tile_id: &TileId) -> Option<TileAction> {
+        self.inner.lock().tiles.get(tile_id).cloned()
+    }
+
+    /// Finds the displays for `action_id` from the plugin `plugin_id` in the
+    /// latest device snapshots, optionally only including the displays on `device_id`
+    pub fn displays(
+        &self,
+        plugin_id: &str,
+        action_id: &str,
+        device_id: Option<DeviceId>,
+    ) -> Vec<DisplayContext> {
+        let inner = &*self.inner.lock();
+
+        inner
+            .devices
+            .iter()
+            .filter(|(id, _)| device_id.is_none_or(|device_id| device_id.eq(*id)))
+            .flat_map(|(device_id, tiles)| {
+                tiles
+                    .iter()
+                    .filter(|tile| {
+                        tile.plugin_id == plugin_id
+                            && tile.action_id == action_id
+                            && matches!(tile.config.icon, TileIcon::Display { .. })
+                    })
+                    .map(|tile| DisplayContext {
+                        device_id: *device_id,
+                        plugin_id: tile.plugin_id.clone(),
+                        action_id: tile.action_id.clone(),
+                        tile_id: tile.id,
+                    })
+            })
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        let inner = &mut *self.inner.lock();
+        inner.tiles.clear();
+        inner.devices.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::protocol::{TileConfig, TileLabel, TilePosition};
+
+    fn tile(id: TileId, plugin_id: &str, icon: TileIcon) -> TileModel {
+        TileModel {
+            id,
+            config: TileConfig {
+                icon,
+                label: TileLabel::default(),
+                icon_options: None,
+            },
+            properties: Default::default(),
+            folder_id: Uuid::new_v4(),
+            plugin_id: plugin_id.to_string(),
+            action_id: "action".to_string(),
+            position: TilePosition {
+                row: 0,
+                column: 0,
+                row_span: 1,
+                column_span: 1,
+            },
+        }
+    }
+
+    fn device_tiles(device_id: DeviceId, tiles: Vec<TileModel>) -> ServerPluginMessage {
+        ServerPluginMessage::DeviceTiles { device_id, tiles }
+    }
+
+    #[test]
+    fn forgets_tiles_removed_from_device() {
+        let known = KnownTiles::default();
+        let (device_a, device_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (shared, removed) = (Uuid::new_v4(), Uuid::new_v4());
+
+        known.apply(&device_tiles(
+            device_a,
+            vec![
+                tile(shared, "plugin", TileIcon::None),
+                tile(removed, "plugin", TileIcon::None),
+            ],
+        ));
+        known.apply(&device_tiles(
+            device_b,
+            vec![tile(shared, "plugin", TileIcon::None)],
+        ));
+        known.apply(&device_tiles(device_a, Vec::new()));
+
+        assert!(known.get(&shared).is_some());
+        assert!(known.get(&removed).is_none());
+    }
+
+    #[test]
+    fn forgets_tiles_no_longer_visible() {
+        let known = KnownTiles::default();
+        let (visible, hidden, clicked) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        known.insert(clicked, "plugin", "action");
+        known.apply(&ServerPluginMessage::VisibleTiles {
+            tiles: vec![
+                tile(visible, "plugin", TileIcon::None),
+                tile(hidden, "plugin", TileIcon::None),
+            ],
+        });
+        known.apply(&ServerPluginMessage::VisibleTiles {
+            tiles: vec![tile(visible, "plugin", TileIcon::None)],
+        });
+
+        assert!(known.get(&visible).is_some());
+        assert!(known.get(&hidden).is_none());
+        assert!(known.get(&clicked).is_none());
+    }
+
+    #[test]
+    fn displays_only_include_plugin_tiles() {
+        let known = KnownTiles::default();
+        let device_id = Uuid::new_v4();
+        let (own, other, icon) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let display = || TileIcon::Display {
+            path: "display.html".to_string(),
+        };
+
+        known.apply(&device_tiles(
+            device_id,
+            vec![
+                tile(own, "plugin", display()),
+                tile(other, "other", display()),
+                tile(icon, "plugin", TileIcon::None),
+            ],
+        ));
+
+        let displays = known.displays("plugin", "action", None);
+        assert_eq!(displays.len(), 1);
+        assert_eq!(displays[0].tile_id, own);
+        assert!(
+            known
+                .displays("plugin", "action", Some(Uuid::new_v4()))
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use serde_json::json;
+use tilepad_plugin_sdk::{DeepLinkContext, DeepLinkUrl, Plugin, PluginSessionHandle, run_plugin};
+use tokio::{net::TcpListener, sync::oneshot, task::LocalSet};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+const PLUGIN_ID: &str = "com.example.plugin";
+
+/// Plugin that starts an authorization flow once registered
+struct AuthPlugin {
+    result: Option<oneshot::Sender<DeepLinkContext>>,
+}
+
+impl Plugin for AuthPlugin {
+    fn on_registered(&mut self, session: &PluginSessionHandle) {
+        let session = session.clone();
+        let result = self.result.take().expect("registered more than once");
+
+        tokio::task::spawn_local(async move {
+            let ctx = session
+                .await_deep_link(Duration::from_secs(5), |state, callback| {
+                    let redirect = callback.path("/auth/callback").query("state", state);
+                    let redirect = redirect.build();
+                    let redirect = utf8_percent_encode(&redirect, NON_ALPHANUMERIC);
+                    format!("https://example.com/authorize?state={state}&redirect_uri={redirect}")
+                })
+                .await
+                .expect("failed to receive deep link");
+
+            _ = result.send(ctx);
+        });
+    }
+}
+
+/// Extracts and decodes the query parameter `key` from `url`
+fn query_param(url: &str, key: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+    query.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        (name == key).then(|| percent_decode_str(value).decode_utf8_lossy().into_owned())
+    })
+}
+
+/// Stand-in for the Tilepad plugin server, registers the plugin then acts as
+/// the browser and authorization service by opening the redirect URI along
+/// with an unrelated deep link that must not resolve the flow
+async fn run_server(listener: TcpListener) {
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut socket = accept_async(stream).await.unwrap();
+
+    while let Some(Ok(message)) = socket.next().await {
+        let Ok(text) = message.to_text() else {
+            continue;
+        };
+        let message: serde_json::Value = serde_json::from_str(text).unwrap();
+
+        let replies = match message["type"].as_str() {
+            Some("RegisterPlugin") => vec![json!({
+                "type": "Registered",
+                "plugin_id": PLUGIN_ID,
+                "host_version": "0.9.0",
+                "protocol_version": 1,
+                "capabilities": [],
+            })],
+            Some("OpenUrl") => {
+                let url = message["url"].as_str().unwrap();
+                let state = query_param(url, "state").unwrap();
+                let redirect = query_param(url, "redirect_uri").unwrap();
+                let redirect = DeepLinkUrl::parse(&redirect).unwrap();
+                assert_eq!(redirect.plugin_id(), PLUGIN_ID);
+                assert_eq!(redirect.segments(), ["auth", "callback"]);
+                assert_eq!(redirect.query_params(), [("state".to_string(), state)]);
+
+                let other = DeepLinkUrl::new(PLUGIN_ID)
+                    .path("/auth/callback")
+                    .query("state", "other");
+                let callback = redirect.query("code", "abc 123");
+
+                vec![
+                    json!({ "type": "DeepLink", "ctx": other.to_context() }),
+                    json!({ "type": "DeepLink", "ctx": callback.to_context() }),
+                ]
+            }
+            _ => Vec::new(),
+        };
+
+        for reply in replies {
+            socket.send(Message::text(reply.to_string())).await.unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn await_deep_link_resolves_from_redirect() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(run_server(listener));
+
+    let (tx, rx) = oneshot::channel();
+    let plugin = AuthPlugin { result: Some(tx) };
+
+    let ctx = LocalSet::new()
+        .run_until(async move {
+            tokio::select! {
+                _ = run_plugin(plugin, PLUGIN_ID, &url) => panic!("plugin stopped"),
+                ctx = rx => ctx.unwrap(),
+            }
+        })
+        .await;
+
+    let ctx = DeepLinkUrl::parse(&ctx.url).unwrap();
+    assert_eq!(ctx.segments(), ["auth", "callback"]);
+
+    let query = ctx.query_params();
+    assert_eq!(query.len(), 2);
+    assert_eq!(query[0].0, "state");
+    assert_ne!(query[0].1, "other");
+    assert_eq!(query[1], ("code".to_string(), "abc 123".to_string()));
+}